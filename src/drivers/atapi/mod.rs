@@ -1,14 +1,15 @@
 pub mod interrupt;
 mod scsi;
 
-use crate::{println, serial_println};
+use crate::drivers::block::{BlockDevice, BLOCK_DEVICES};
+use crate::println;
+use crate::syscalls::errno::{Errno, SysResult};
 use interrupt::INTERRUPT_FUTURE;
 use scsi::SCSIPacket;
 
+use alloc::boxed::Box;
+use async_trait::async_trait;
 use core::convert::TryInto;
-
-use crate::utils::AsyncMutex;
-use lazy_static::lazy_static;
 use x86_64::instructions::port::Port;
 
 const CD_SECTOR_SIZE: usize = 2048;
@@ -25,10 +26,10 @@ const ATA_DRIVE_SLAVE: u8 = 0xb0;
 const ATA_CMD_PACKET: u8 = 0xa0;
 
 // SCSI commands
+const SCSI_READ_CAPACITY: u8 = 0x25;
 const SCSI_READ_12: u8 = 0xa8;
 
 // Status bits
-#[allow(dead_code)]
 const ATA_ERR: u8 = 1 << 0;
 const ATA_DRQ: u8 = 1 << 3;
 #[allow(dead_code)]
@@ -57,15 +58,13 @@ static ATAPI_SIG: [u8; 4] = [
     ATAPI_SIG_LBA_HI,
 ];
 
-lazy_static! {
-    pub static ref DRIVE: AsyncMutex<Option<ATABus>> =
-        AsyncMutex::new(ATABus::discover_atapi_drive());
-}
-
 pub async fn init() {
     println!("Detecting drives");
-    match DRIVE.lock().await.as_ref() {
-        None => println!("No drive detected :("),
+    let mut drive = match ATABus::discover_atapi_drive() {
+        None => {
+            println!("No drive detected :(");
+            return;
+        }
         Some(drive) => {
             let drive_type = match drive.current_drive {
                 ATA_DRIVE_MASTER => "master",
@@ -78,9 +77,20 @@ pub async fn init() {
                 _ => "bad",
             };
             println!("Detected {} drive on {} bus", drive_type, bus);
+            drive
         }
     };
     INTERRUPT_FUTURE.pop();
+
+    // The pending interrupt must be cleared before sending commands
+    drive.capacity = drive.read_capacity().await.unwrap_or(0);
+    let res = BLOCK_DEVICES
+        .lock()
+        .await
+        .register("cdrom", Box::new(drive));
+    if let Err(err) = res {
+        println!("Could not register the drive: {}", err);
+    }
 }
 
 #[derive(Debug)]
@@ -102,6 +112,7 @@ pub struct ATABus {
     dcr: Port<u8>,
 
     current_drive: u8,
+    capacity: u64, // Number of sectors of the disc, 0 if unknown
 
     pub block: [u8; CD_SECTOR_SIZE],
 }
@@ -162,6 +173,7 @@ impl ATABus {
             dcr: Port::new(port + 0x206),
 
             current_drive: 0,
+            capacity: 0,
 
             block: [0; CD_SECTOR_SIZE],
         }
@@ -282,6 +294,34 @@ impl ATABus {
         self.block
     }
 
+    // Number of sectors of the disc in the drive
+    async fn read_capacity(&mut self) -> Option<u64> {
+        let mut packet = SCSIPacket::new();
+        packet.op_code = SCSI_READ_CAPACITY;
+
+        self.send_packet(packet);
+        (*INTERRUPT_FUTURE).await;
+
+        // No disc in the drive
+        if unsafe { self.status.read() } & ATA_ERR != 0 {
+            return None;
+        }
+
+        // Big endian last sector index, followed by the sector size
+        let mut data: [u8; 8] = [0; 8];
+        for i in (0..data.len()).step_by(2) {
+            unsafe {
+                let bytes: [u8; 2] = self.data.read().to_le_bytes();
+                data[i] = bytes[0];
+                data[i + 1] = bytes[1];
+            }
+        }
+        self.wait_command_end();
+
+        let last_lba = u32::from_be_bytes(data[..4].try_into().unwrap());
+        Some(last_lba as u64 + 1)
+    }
+
     fn wait_busy(&mut self) {
         let mut status = ATA_BSY;
         while (status & ATA_BSY) != 0 {
@@ -321,12 +361,24 @@ impl ATABus {
     }
 }
 
-#[allow(dead_code)]
-pub async fn print_block(lba: u32) {
-    let block = DRIVE.lock().await.as_mut().unwrap().read_block(lba).await;
-    serial_println!("{:x?}", block);
-}
+#[async_trait(?Send)]
+impl BlockDevice for ATABus {
+    fn block_size(&self) -> usize {
+        CD_SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> u64 {
+        self.capacity
+    }
 
-pub async fn read_block(lba: u32) -> [u8; CD_SECTOR_SIZE] {
-    DRIVE.lock().await.as_mut().unwrap().read_block(lba).await
+    async fn read_blocks(&mut self, lba: u64, count: usize, buf: &mut [u8]) -> SysResult<()> {
+        if buf.len() < count * CD_SECTOR_SIZE {
+            return Err(Errno::EINVAL);
+        }
+        for i in 0..count {
+            let block = self.read_block((lba + i as u64) as u32).await;
+            buf[i * CD_SECTOR_SIZE..(i + 1) * CD_SECTOR_SIZE].copy_from_slice(&block);
+        }
+        Ok(())
+    }
 }
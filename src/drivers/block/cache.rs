@@ -0,0 +1,152 @@
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::utils::AsyncMutex;
+
+use super::{DeviceId, BLOCK_DEVICES, BLOCK_SIZE};
+
+use alloc::{boxed::Box, collections::BTreeMap};
+use lazy_static::lazy_static;
+
+const BLOCK_CACHE_CAPACITY: usize = 32; // In blocks
+
+lazy_static! {
+    pub static ref BLOCK_CACHE: AsyncMutex<BlockCache> =
+        AsyncMutex::new(BlockCache::new(BLOCK_CACHE_CAPACITY));
+}
+
+struct CachedBlock {
+    data: Box<[u8; BLOCK_SIZE]>,
+    last_use: u64,
+}
+
+// Least recently used blocks are evicted once the capacity is reached
+pub struct BlockCache {
+    blocks: BTreeMap<(DeviceId, u32), CachedBlock>,
+    capacity: usize,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        BlockCache {
+            blocks: BTreeMap::new(),
+            capacity,
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get(&mut self, device: DeviceId, lba: u32) -> Option<[u8; BLOCK_SIZE]> {
+        self.clock += 1;
+        match self.blocks.get_mut(&(device, lba)) {
+            Some(block) => {
+                self.hits += 1;
+                block.last_use = self.clock;
+                Some(*block.data)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, device: DeviceId, lba: u32, data: &[u8; BLOCK_SIZE]) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.blocks.contains_key(&(device, lba)) && self.blocks.len() >= self.capacity {
+            self.evict();
+        }
+        self.clock += 1;
+        self.blocks.insert(
+            (device, lba),
+            CachedBlock {
+                data: Box::new(*data),
+                last_use: self.clock,
+            },
+        );
+    }
+
+    fn evict(&mut self) {
+        let lru = self
+            .blocks
+            .iter()
+            .min_by_key(|(_, block)| block.last_use)
+            .map(|(key, _)| *key);
+        if let Some(key) = lru {
+            self.blocks.remove(&key);
+        }
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+// Pages of file mappings are read from the page fault handler, where the locks
+// held by the executor tasks fail the read with EBUSY instead of being waited on
+pub async fn read_block(device: DeviceId, lba: u32) -> SysResult<[u8; BLOCK_SIZE]> {
+    let cached = BLOCK_CACHE
+        .lock_or_fail()
+        .await
+        .ok_or(Errno::EBUSY)?
+        .get(device, lba);
+    if let Some(block) = cached {
+        return Ok(block);
+    }
+
+    let mut block = [0; BLOCK_SIZE];
+    let handle = BLOCK_DEVICES
+        .lock_or_fail()
+        .await
+        .ok_or(Errno::EBUSY)?
+        .get(device)
+        .ok_or(Errno::ENXIO)?;
+
+    // The cache is not locked during the device access
+    let mut dev = handle.lock_or_fail().await.ok_or(Errno::EBUSY)?;
+    let ratio = BLOCK_SIZE / dev.block_size();
+    dev.read_blocks(lba as u64 * ratio as u64, ratio, &mut block)
+        .await?;
+    drop(dev);
+
+    if let Some(mut cache) = BLOCK_CACHE.lock_or_fail().await {
+        cache.insert(device, lba, &block);
+    }
+    Ok(block)
+}
+
+// Read whole blocks straight into the buffer, without going through the cache,
+// so that large reads do not evict the blocks in use
+pub async fn read_blocks(device: DeviceId, lba: u32, buf: &mut [u8]) -> SysResult<()> {
+    let handle = BLOCK_DEVICES
+        .lock_or_fail()
+        .await
+        .ok_or(Errno::EBUSY)?
+        .get(device)
+        .ok_or(Errno::ENXIO)?;
+    let mut dev = handle.lock_or_fail().await.ok_or(Errno::EBUSY)?;
+    let ratio = BLOCK_SIZE / dev.block_size();
+    let count = buf.len() / BLOCK_SIZE;
+    dev.read_blocks(
+        lba as u64 * ratio as u64,
+        count * ratio,
+        &mut buf[..count * BLOCK_SIZE],
+    )
+    .await
+}
@@ -0,0 +1,106 @@
+use crate::fd::FDt;
+use crate::fs::{FileType, VIRTUAL_FS};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::utils::AsyncMutex;
+
+use super::{BlockDevice, DeviceId, BLOCK_DEVICES, BLOCK_SIZE};
+
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String};
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    // Backing file of the attached loop devices, closed when detaching them
+    static ref LOOP_FILES: AsyncMutex<BTreeMap<DeviceId, FDt>> = AsyncMutex::new(BTreeMap::new());
+}
+
+// Block device reading a file, through its descriptor
+pub struct LoopDevice {
+    fd: FDt,
+    size: u64,
+}
+
+#[async_trait(?Send)]
+impl BlockDevice for LoopDevice {
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    // The last block is completed with zeros
+    fn block_count(&self) -> u64 {
+        self.size.div_ceil(BLOCK_SIZE as u64)
+    }
+
+    async fn read_blocks(&mut self, lba: u64, count: usize, buf: &mut [u8]) -> SysResult<()> {
+        let len = count * BLOCK_SIZE;
+        let buf = buf.get_mut(..len).ok_or(Errno::EINVAL)?;
+        let mut offset = lba * BLOCK_SIZE as u64;
+        let mut read: usize = 0;
+        while read < len && offset < self.size {
+            let n = self
+                .fd
+                .lock_or_fail()
+                .await
+                .ok_or(Errno::EBUSY)?
+                .pread(&mut buf[read..], len - read, offset)
+                .await?;
+            if n == 0 {
+                break;
+            }
+            read += n;
+            offset += n as u64;
+        }
+        buf[read..].fill(0);
+        Ok(())
+    }
+}
+
+/// Registers a read only block device backed by the open file, named loopN
+/// after the first free number. The descriptor is closed when detaching it.
+pub async fn attach(fd: FDt) -> SysResult<(String, DeviceId)> {
+    let stat = fd.lock().await.fstat().await?;
+    if stat.file_type != FileType::File {
+        return Err(Errno::EINVAL);
+    }
+
+    let mut devices = BLOCK_DEVICES.lock().await;
+    let name = (0..)
+        .map(|n| format!("loop{}", n))
+        .find(|name| devices.lookup(name).is_none())
+        .unwrap();
+    let device = LoopDevice {
+        fd: fd.clone(),
+        size: stat.size,
+    };
+    let id = devices.register(&name, Box::new(device))?;
+    drop(devices);
+
+    LOOP_FILES.lock().await.insert(id, fd);
+    Ok((name, id))
+}
+
+/// Unregisters a loop device, once no file system is mounted from it,
+/// and closes its backing file.
+pub async fn detach(id: DeviceId) -> SysResult<()> {
+    if !LOOP_FILES.lock().await.contains_key(&id) {
+        return Err(Errno::ENXIO);
+    }
+    // The VFS is locked first, as when opening a device through the DevFS
+    let vfs = VIRTUAL_FS.lock().await;
+    let mut devices = BLOCK_DEVICES.lock().await;
+    let name = devices.name(id).map(String::from);
+    let mounted = vfs
+        .mount_table()
+        .iter()
+        .any(|m| m.source.is_some() && m.source == name);
+    if mounted {
+        return Err(Errno::EBUSY);
+    }
+    devices.unregister(id);
+    drop(devices);
+    drop(vfs);
+
+    let fd = LOOP_FILES.lock().await.remove(&id).ok_or(Errno::ENXIO)?;
+    fd.lock().await.close().await;
+    Ok(())
+}
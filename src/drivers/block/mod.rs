@@ -0,0 +1,25 @@
+pub mod cache;
+pub mod loop_device;
+pub mod registry;
+
+pub use cache::{read_block, read_blocks};
+pub use registry::BLOCK_DEVICES;
+
+use crate::syscalls::errno::SysResult;
+
+use alloc::boxed::Box;
+use async_trait::async_trait;
+
+// Size of the blocks handed out by the cache, device blocks must divide it
+pub const BLOCK_SIZE: usize = 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeviceId(pub u32);
+
+// Devices are read only, none of the drivers can write yet
+#[async_trait(?Send)]
+pub trait BlockDevice {
+    fn block_size(&self) -> usize;
+    fn block_count(&self) -> u64;
+    async fn read_blocks(&mut self, lba: u64, count: usize, buf: &mut [u8]) -> SysResult<()>;
+}
@@ -0,0 +1,80 @@
+use crate::println;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::utils::AsyncMutex;
+
+use super::{BlockDevice, DeviceId, BLOCK_SIZE};
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
+
+pub type BlockDevicet = Arc<AsyncMutex<Box<dyn BlockDevice>>>;
+
+lazy_static! {
+    pub static ref BLOCK_DEVICES: AsyncMutex<BlockDeviceRegistry> =
+        AsyncMutex::new(BlockDeviceRegistry::new());
+}
+
+struct RegisteredDevice {
+    name: String,
+    device: BlockDevicet,
+}
+
+// Block devices by identifier, along with the name they are looked up by
+pub struct BlockDeviceRegistry {
+    devices: BTreeMap<DeviceId, RegisteredDevice>,
+    next_id: u32,
+}
+
+impl BlockDeviceRegistry {
+    pub fn new() -> Self {
+        BlockDeviceRegistry {
+            devices: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    // The cache reads whole blocks, made of a whole number of device blocks
+    pub fn register(&mut self, name: &str, device: Box<dyn BlockDevice>) -> SysResult<DeviceId> {
+        let block_size = device.block_size();
+        if block_size == 0 || !BLOCK_SIZE.is_multiple_of(block_size) {
+            return Err(Errno::EINVAL);
+        }
+        let id = DeviceId(self.next_id);
+        self.next_id += 1;
+        self.devices.insert(
+            id,
+            RegisteredDevice {
+                name: String::from(name),
+                device: Arc::new(AsyncMutex::new(device)),
+            },
+        );
+        println!("Registered block device {}: {:?}", name, id);
+        Ok(id)
+    }
+
+    pub fn unregister(&mut self, id: DeviceId) {
+        self.devices.remove(&id);
+    }
+
+    pub fn get(&self, id: DeviceId) -> Option<BlockDevicet> {
+        self.devices.get(&id).map(|d| d.device.clone())
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<DeviceId> {
+        self.devices
+            .iter()
+            .find(|(_, d)| d.name == name)
+            .map(|(id, _)| *id)
+    }
+
+    pub fn name(&self, id: DeviceId) -> Option<&str> {
+        self.devices.get(&id).map(|d| d.name.as_str())
+    }
+
+    pub fn names(&self) -> Vec<(DeviceId, String)> {
+        self.devices
+            .iter()
+            .map(|(id, d)| (*id, d.name.clone()))
+            .collect()
+    }
+}
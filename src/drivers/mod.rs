@@ -1,3 +1,4 @@
 pub mod atapi;
+pub mod block;
 pub mod serial;
 pub mod vga;
@@ -21,6 +21,7 @@ const CLEAR_RECEIVE_FIFO: u8 = 0x1 << 1;
 const NO_PARITY: u8 = 0x0;
 const EIGHT_BITS_LENGTH: u8 = 0x3;
 
+const DATA_READY: u8 = 0x1;
 const EMPTY_TRANSMITTER: u8 = 0x1 << 5;
 const DLAB: u8 = 0x1 << 7;
 
@@ -93,9 +94,27 @@ impl SerialPort {
         }
     }
 
+    pub fn data_ready(&mut self) -> bool {
+        unsafe { self.line_status.read() & DATA_READY != 0 }
+    }
+
+    pub fn read_byte(&mut self) -> Option<u8> {
+        unsafe {
+            let status: u8 = self.line_status.read();
+            match status & DATA_READY {
+                0 => None,
+                _ => Some(self.base.read()),
+            }
+        }
+    }
+
     fn write_string(&mut self, s: &str) -> usize {
+        self.write_bytes(s.as_bytes())
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> usize {
         let mut len: usize = 0;
-        for byte in s.bytes() {
+        for &byte in bytes {
             let mut written: bool = self.write_byte(byte);
             while !written {
                 written = self.write_byte(byte);
@@ -1,15 +1,23 @@
+use crate::fs::{DirEntry, Stat};
 use crate::println;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{POLLIN, POLLOUT, SEEK_CUR, SEEK_END, SEEK_SET};
 use crate::utils::AsyncMutex;
 
 use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
 use async_trait::async_trait;
-use core::cell::RefCell;
+use core::convert::TryFrom;
 use core::sync::atomic::{AtomicU32, Ordering};
 use lazy_static::lazy_static;
 
-pub type FDt = Arc<RefCell<dyn FileDescriptor>>;
+pub mod pipe;
+pub mod poll;
+pub mod table;
+
+pub type FDt = Arc<AsyncMutex<dyn FileDescriptor>>;
 
 lazy_static! {
+    // Every open file of the system, the descriptors of a process are in its own table
     pub static ref FD_TABLE: AsyncMutex<FDTable> = AsyncMutex::new(FDTable::new());
 }
 
@@ -18,7 +26,6 @@ pub struct FDId(u32);
 
 impl FDId {
     pub fn new() -> Self {
-        // TODO: search for first available fd
         static NEXT_ID: AtomicU32 = AtomicU32::new(0);
         FDId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
     }
@@ -40,20 +47,98 @@ impl FDTable {
         println!("Unregistered fd: {:?}", fd.get_fd());
     }
 
-    pub fn register_fd(&mut self, fd: FDt) {
-        self.table.insert(fd.borrow().get_fd(), fd.clone());
-        println!(
-            "Registered fd: {:?}",
-            self.table.get(&FDId(0)).unwrap().borrow().get_fd()
-        );
+    pub fn contains(&self, fd: &FDId) -> bool {
+        self.table.contains_key(fd)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&FDId, &FDt)> {
+        self.table.iter()
+    }
+
+    pub fn register_fd(&mut self, id: FDId, fd: FDt) {
+        self.table.insert(id, fd);
+        println!("Registered fd: {:?}", id);
     }
 }
 
-#[async_trait]
+#[async_trait(?Send)]
 pub trait FileDescriptor {
     fn get_fd(&self) -> FDId;
-    async fn write(&mut self, buf: &[u8], count: usize) -> isize;
-    async fn read(&mut self, buf: &mut [u8], count: usize) -> isize;
+    async fn write(&mut self, buf: &[u8], count: usize) -> SysResult<usize>;
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize>;
+
+    // Read at an offset, leaving the descriptor offset unchanged
+    async fn pread(&mut self, buf: &mut [u8], count: usize, offset: u64) -> SysResult<usize> {
+        let offset = i64::try_from(offset).map_err(|_| Errno::EINVAL)?;
+        let current = self.lseek(0, SEEK_CUR).await?;
+        self.lseek(offset, SEEK_SET).await?;
+        let res = self.read(buf, count).await;
+        self.lseek(current as i64, SEEK_SET).await?;
+        res
+    }
+
+    async fn pwrite(&mut self, buf: &[u8], count: usize, offset: u64) -> SysResult<usize> {
+        let offset = i64::try_from(offset).map_err(|_| Errno::EINVAL)?;
+        let current = self.lseek(0, SEEK_CUR).await?;
+        self.lseek(offset, SEEK_SET).await?;
+        let res = self.write(buf, count).await;
+        self.lseek(current as i64, SEEK_SET).await?;
+        res
+    }
+
+    // Fill the buffers in order, stopping at the first short read
+    async fn readv(&mut self, iov: &mut [&mut [u8]]) -> SysResult<usize> {
+        let mut read: usize = 0;
+        for buf in iov.iter_mut() {
+            let len = buf.len();
+            let n = self.read(buf, len).await?;
+            read += n;
+            if n < len {
+                break;
+            }
+        }
+        Ok(read)
+    }
+
+    async fn writev(&mut self, iov: &[&[u8]]) -> SysResult<usize> {
+        let mut written: usize = 0;
+        for buf in iov.iter() {
+            let n = self.write(buf, buf.len()).await?;
+            written += n;
+            if n < buf.len() {
+                break;
+            }
+        }
+        Ok(written)
+    }
     async fn close(&mut self);
-    async fn lseek(&mut self, offset: i32, whence: u32) -> i32;
+
+    // Poll events currently set, regular files never block
+    fn readiness(&self) -> u16 {
+        POLLIN | POLLOUT
+    }
+    async fn lseek(&mut self, offset: i64, whence: u32) -> SysResult<u64>;
+    // None once every entry was read
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>>;
+    async fn fstat(&self) -> SysResult<Stat>;
+    async fn ftruncate(&mut self, length: usize) -> SysResult<()>;
+}
+
+// New offset of a descriptor after lseek, rejecting unknown whence values
+// and offsets before the start of the file
+pub fn seek_offset(current: u64, size: u64, offset: i64, whence: u32) -> SysResult<u64> {
+    let base = match whence {
+        w if w == SEEK_SET => 0,
+        w if w == SEEK_CUR => current,
+        w if w == SEEK_END => size,
+        _ => return Err(Errno::EINVAL),
+    };
+    let new_offset = i64::try_from(base)
+        .ok()
+        .and_then(|base| base.checked_add(offset))
+        .ok_or(Errno::EINVAL)?;
+    if new_offset < 0 {
+        return Err(Errno::EINVAL);
+    }
+    Ok(new_offset as u64)
 }
@@ -0,0 +1,224 @@
+use crate::fs::{DirEntry, FileType, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{POLLERR, POLLHUP, POLLIN, POLLOUT, S_IFIFO};
+use crate::utils::AsyncMutex;
+
+use super::{FDId, FDt, FileDescriptor, FD_TABLE};
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc};
+use async_trait::async_trait;
+use core::cell::RefCell;
+use core::task::Poll;
+use futures_util::future::poll_fn;
+use futures_util::task::AtomicWaker;
+
+const PIPE_SIZE: usize = 4096; // Bytes buffered before writers block
+
+struct PipeState {
+    buffer: VecDeque<u8>,
+    readers: usize, // Open read ends
+    writers: usize, // Open write ends
+}
+
+// Buffer shared by both ends, each end wakes the task waiting on the other
+struct Pipe {
+    state: RefCell<PipeState>,
+    read_waker: AtomicWaker,
+    write_waker: AtomicWaker,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PipeEnd {
+    Read,
+    Write,
+}
+
+pub struct PipeFD {
+    pub fd: FDId,
+    pipe: Arc<Pipe>,
+    end: PipeEnd,
+}
+
+// Read and write ends of a new pipe
+pub async fn pipe() -> (FDt, FDt) {
+    let pipe = Arc::new(Pipe {
+        state: RefCell::new(PipeState {
+            buffer: VecDeque::with_capacity(PIPE_SIZE),
+            readers: 1,
+            writers: 1,
+        }),
+        read_waker: AtomicWaker::new(),
+        write_waker: AtomicWaker::new(),
+    });
+    let reader = PipeFD::new(pipe.clone(), PipeEnd::Read).await;
+    let writer = PipeFD::new(pipe, PipeEnd::Write).await;
+    (reader, writer)
+}
+
+impl PipeFD {
+    async fn new(pipe: Arc<Pipe>, end: PipeEnd) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(PipeFD { fd: id, pipe, end }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+impl Pipe {
+    // Bytes read, or None when the pipe is empty and writers remain
+    fn try_read(&self, buf: &mut [u8]) -> Option<usize> {
+        let mut state = self.state.borrow_mut();
+        if state.buffer.is_empty() {
+            return match state.writers {
+                0 => Some(0), // End of file
+                _ => None,
+            };
+        }
+        let count = core::cmp::min(buf.len(), state.buffer.len());
+        for (dst, src) in buf.iter_mut().zip(state.buffer.drain(..count)) {
+            *dst = src;
+        }
+        self.write_waker.wake();
+        Some(count)
+    }
+
+    // Bytes written, or None when the pipe is full
+    fn try_write(&self, buf: &[u8]) -> Option<SysResult<usize>> {
+        let mut state = self.state.borrow_mut();
+        if state.readers == 0 {
+            return Some(Err(Errno::EPIPE));
+        }
+        let count = core::cmp::min(buf.len(), PIPE_SIZE - state.buffer.len());
+        if count == 0 {
+            return None;
+        }
+        state.buffer.extend(&buf[..count]);
+        self.read_waker.wake();
+        Some(Ok(count))
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for PipeFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    // Waits until every byte is buffered, or the last reader is gone
+    async fn write(&mut self, buf: &[u8], count: usize) -> SysResult<usize> {
+        if self.end != PipeEnd::Write {
+            return Err(Errno::EBADF);
+        }
+        let count = core::cmp::min(count, buf.len());
+        let pipe = &self.pipe;
+        let mut written: usize = 0;
+        poll_fn(|cx| {
+            while written < count {
+                let res = match pipe.try_write(&buf[written..count]) {
+                    Some(res) => res,
+                    None => {
+                        pipe.write_waker.register(cx.waker());
+                        // A reader may have drained the pipe meanwhile
+                        match pipe.try_write(&buf[written..count]) {
+                            Some(res) => res,
+                            None => return Poll::Pending,
+                        }
+                    }
+                };
+                match res {
+                    Ok(n) => written += n,
+                    Err(_) if written > 0 => break,
+                    Err(err) => return Poll::Ready(Err(err)),
+                }
+            }
+            Poll::Ready(Ok(written))
+        })
+        .await
+    }
+
+    // Waits for a first byte, then returns what is buffered
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        if self.end != PipeEnd::Read {
+            return Err(Errno::EBADF);
+        }
+        let count = core::cmp::min(count, buf.len());
+        if count == 0 {
+            return Ok(0);
+        }
+        let pipe = &self.pipe;
+        let buf = &mut buf[..count];
+        poll_fn(|cx| {
+            if let Some(read) = pipe.try_read(buf) {
+                return Poll::Ready(Ok(read));
+            }
+            pipe.read_waker.register(cx.waker());
+            match pipe.try_read(buf) {
+                Some(read) => Poll::Ready(Ok(read)),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    // Waiters on the other end see end of file or a broken pipe
+    async fn close(&mut self) {
+        {
+            let mut state = self.pipe.state.borrow_mut();
+            match self.end {
+                PipeEnd::Read => state.readers -= 1,
+                PipeEnd::Write => state.writers -= 1,
+            }
+        }
+        match self.end {
+            PipeEnd::Read => self.pipe.write_waker.wake(),
+            PipeEnd::Write => self.pipe.read_waker.wake(),
+        }
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    // Buffered data stays readable after the writers are gone
+    fn readiness(&self) -> u16 {
+        let state = self.pipe.state.borrow();
+        match self.end {
+            PipeEnd::Read => {
+                let mut events = 0;
+                if !state.buffer.is_empty() {
+                    events |= POLLIN;
+                }
+                if state.writers == 0 {
+                    events |= POLLHUP;
+                }
+                events
+            }
+            PipeEnd::Write if state.readers == 0 => POLLERR,
+            PipeEnd::Write if state.buffer.len() < PIPE_SIZE => POLLOUT,
+            PipeEnd::Write => 0,
+        }
+    }
+
+    async fn lseek(&mut self, _offset: i64, _whence: u32) -> SysResult<u64> {
+        Err(Errno::ESPIPE)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        Err(Errno::ENOTDIR)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(Stat {
+            file_type: FileType::Fifo,
+            size: self.pipe.state.borrow().buffer.len() as u64,
+            block: 0,
+            mtime: 0,
+            mode: S_IFIFO | 0o600,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+        })
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EINVAL)
+    }
+}
@@ -0,0 +1,46 @@
+use crate::interrupts::pic::pit;
+use crate::syscalls::io::{POLLERR, POLLHUP, POLLNVAL};
+
+use super::FDt;
+
+use core::task::Poll;
+use futures_util::future::poll_fn;
+
+pub struct PollFd {
+    pub fd: FDt,
+    pub events: u16,  // Requested events
+    pub revents: u16, // Returned events
+}
+
+// Errors and hang-ups are reported even when not requested
+fn check(fds: &mut [PollFd]) -> usize {
+    let mut ready: usize = 0;
+    for pollfd in fds.iter_mut() {
+        // Descriptors locked by a blocked read or write are not ready
+        let events = match pollfd.fd.try_lock() {
+            Some(fd) => fd.readiness(),
+            None => 0,
+        };
+        pollfd.revents = events & (pollfd.events | POLLERR | POLLHUP | POLLNVAL);
+        if pollfd.revents != 0 {
+            ready += 1;
+        }
+    }
+    ready
+}
+
+// Wait until one of the descriptors is ready, or for timeout PIT ticks,
+// forever if None. Returns the number of descriptors with events set.
+// Readiness is checked again on every tick, as devices do not notify pollers.
+pub async fn poll(fds: &mut [PollFd], timeout: Option<u64>) -> usize {
+    let deadline = timeout.map(|ticks| pit::gettick() + ticks);
+    poll_fn(|cx| {
+        let ready = check(fds);
+        if ready > 0 || deadline.is_some_and(|deadline| pit::gettick() >= deadline) {
+            return Poll::Ready(ready);
+        }
+        pit::wake_on_tick(cx.waker());
+        Poll::Pending
+    })
+    .await
+}
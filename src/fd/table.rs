@@ -0,0 +1,342 @@
+use crate::fs::{DirEntry, FileSystem, VIRTUAL_FS};
+use crate::proc::thread::ThreadId;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_APPEND, O_RDONLY, O_WRONLY, SEEK_CUR, SEEK_SET};
+use crate::utils::AsyncMutex;
+
+use super::{seek_offset, FDt};
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::cell::{Cell, RefCell};
+use core::convert::TryFrom;
+use lazy_static::lazy_static;
+
+const MAX_FDS: usize = 256; // Descriptors per process
+const SETFL_FLAGS: u32 = O_APPEND; // Status flags fcntl can change
+
+pub type ProcessFDTablet = Arc<RefCell<ProcessFDTable>>;
+
+lazy_static! {
+    static ref PROCESS_FD_TABLES: AsyncMutex<BTreeMap<ThreadId, ProcessFDTablet>> =
+        AsyncMutex::new(BTreeMap::new());
+}
+
+// Open file shared by the descriptors duplicated from one another,
+// reads and writes go through its offset
+pub struct OpenFile {
+    pub fd: FDt,
+    offset: Cell<u64>,
+    flags: Cell<u32>,                  // Access mode and status flags
+    unread: RefCell<Option<DirEntry>>, // Directory entry given back, listed next
+}
+
+impl OpenFile {
+    pub fn new(fd: FDt, flags: u32) -> Self {
+        OpenFile {
+            fd,
+            offset: Cell::new(0),
+            flags: Cell::new(flags & (O_ACCMODE | SETFL_FLAGS)),
+            unread: RefCell::new(None),
+        }
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags.get()
+    }
+
+    // The access mode is kept, only the status flags change
+    pub fn set_flags(&self, flags: u32) {
+        let kept = self.flags.get() & !SETFL_FLAGS;
+        self.flags.set(kept | flags & SETFL_FLAGS);
+    }
+
+    // Files that cannot seek, as pipes and terminals, are read in order
+    pub async fn read(&self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        let offset = self.offset.get();
+        let res = self.fd.lock().await.pread(buf, count, offset).await;
+        match res {
+            Ok(read) => {
+                self.offset.set(offset + read as u64);
+                Ok(read)
+            }
+            Err(Errno::ESPIPE) => self.fd.lock().await.read(buf, count).await,
+            Err(err) => Err(err),
+        }
+    }
+
+    // Appending writes at the end of the file, whatever the offset
+    pub async fn write(&self, buf: &[u8], count: usize) -> SysResult<usize> {
+        let offset = match self.flags.get() & O_APPEND {
+            0 => self.offset.get(),
+            _ => {
+                let stat = self.fd.lock().await.fstat().await;
+                stat.map_or(self.offset.get(), |stat| stat.size)
+            }
+        };
+        let res = self.fd.lock().await.pwrite(buf, count, offset).await;
+        match res {
+            Ok(written) => {
+                self.offset.set(offset + written as u64);
+                Ok(written)
+            }
+            Err(Errno::ESPIPE) => self.fd.lock().await.write(buf, count).await,
+            Err(err) => Err(err),
+        }
+    }
+
+    // The descriptor is first moved to the offset of the open file
+    pub async fn readv(&self, iov: &mut [&mut [u8]]) -> SysResult<usize> {
+        let mut fd = self.fd.lock().await;
+        let offset = self.offset.get();
+        match fd.lseek(offset as i64, SEEK_SET).await {
+            Ok(_) => {
+                let read = fd.readv(iov).await?;
+                self.offset.set(offset + read as u64);
+                Ok(read)
+            }
+            Err(Errno::ESPIPE) => fd.readv(iov).await,
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn writev(&self, iov: &[&[u8]]) -> SysResult<usize> {
+        let mut fd = self.fd.lock().await;
+        let offset = match self.flags.get() & O_APPEND {
+            0 => self.offset.get(),
+            _ => fd.fstat().await.map_or(self.offset.get(), |stat| stat.size),
+        };
+        match fd.lseek(offset as i64, SEEK_SET).await {
+            Ok(_) => {
+                let written = fd.writev(iov).await?;
+                self.offset.set(offset + written as u64);
+                Ok(written)
+            }
+            Err(Errno::ESPIPE) => fd.writev(iov).await,
+            Err(err) => Err(err),
+        }
+    }
+
+    // Seeks from the offset of the open file are made absolute, the file
+    // checks the other ones against its size
+    pub async fn lseek(&self, offset: i64, whence: u32) -> SysResult<u64> {
+        let (offset, whence) = match whence {
+            w if w == SEEK_CUR => {
+                let offset = seek_offset(self.offset.get(), 0, offset, SEEK_CUR)?;
+                (offset as i64, SEEK_SET)
+            }
+            _ => (offset, whence),
+        };
+        let offset = self.fd.lock().await.lseek(offset, whence).await?;
+        self.offset.set(offset);
+        self.unread.replace(None);
+        Ok(offset)
+    }
+
+    pub async fn readdir(&self) -> SysResult<Option<DirEntry>> {
+        if let Some(entry) = self.unread.replace(None) {
+            return Ok(Some(entry));
+        }
+        self.fd.lock().await.readdir().await
+    }
+
+    // The entry did not fit in the caller buffer
+    pub fn unread(&self, entry: DirEntry) {
+        self.unread.replace(Some(entry));
+    }
+}
+
+// The file is closed along with the last descriptor referring to it
+pub async fn release(file: Arc<OpenFile>) {
+    if let Ok(file) = Arc::try_unwrap(file) {
+        file.fd.lock().await.close().await;
+    }
+}
+
+#[derive(Clone)]
+struct Descriptor {
+    file: Arc<OpenFile>,
+    cloexec: bool, // Closed when the process executes another program
+}
+
+// Descriptors of a process, indexed by the small integers user programs use
+pub struct ProcessFDTable {
+    slots: Vec<Option<Descriptor>>,
+}
+
+impl ProcessFDTable {
+    pub fn new() -> Self {
+        ProcessFDTable { slots: Vec::new() }
+    }
+
+    // Standard input, output and error on the console
+    pub async fn with_console() -> SysResult<Self> {
+        // Standard input is opened apart, a blocked read does not hold up writes
+        let input = VIRTUAL_FS
+            .lock()
+            .await
+            .open("/dev/console", O_RDONLY)
+            .await?;
+        let output = VIRTUAL_FS.lock().await.open("/dev/console", O_WRONLY).await;
+        let output = match output {
+            Ok(output) => Arc::new(OpenFile::new(output, O_WRONLY)),
+            Err(err) => {
+                input.lock().await.close().await;
+                return Err(err);
+            }
+        };
+        let mut table = ProcessFDTable::new();
+        table.insert(Arc::new(OpenFile::new(input, O_RDONLY)), false)?;
+        for _ in 0..2 {
+            table.insert(output.clone(), false)?;
+        }
+        Ok(table)
+    }
+
+    fn index(fd: u64) -> SysResult<usize> {
+        match usize::try_from(fd) {
+            Ok(fd) if fd < MAX_FDS => Ok(fd),
+            _ => Err(Errno::EBADF),
+        }
+    }
+
+    fn descriptor(&self, fd: u64) -> SysResult<&Descriptor> {
+        let index = ProcessFDTable::index(fd)?;
+        match self.slots.get(index) {
+            Some(Some(descriptor)) => Ok(descriptor),
+            _ => Err(Errno::EBADF),
+        }
+    }
+
+    // Lowest free descriptor, from min
+    fn lowest_free(&self, min: usize) -> SysResult<usize> {
+        (min..MAX_FDS)
+            .find(|i| !matches!(self.slots.get(*i), Some(Some(_))))
+            .ok_or(Errno::EMFILE)
+    }
+
+    fn set(&mut self, index: usize, descriptor: Descriptor) -> Option<Arc<OpenFile>> {
+        if self.slots.len() <= index {
+            self.slots.resize(index + 1, None);
+        }
+        self.slots[index]
+            .replace(descriptor)
+            .map(|replaced| replaced.file)
+    }
+
+    pub fn insert(&mut self, file: Arc<OpenFile>, cloexec: bool) -> SysResult<u64> {
+        let index = self.lowest_free(0)?;
+        self.set(index, Descriptor { file, cloexec });
+        Ok(index as u64)
+    }
+
+    pub fn get(&self, fd: u64) -> SysResult<Arc<OpenFile>> {
+        Ok(self.descriptor(fd)?.file.clone())
+    }
+
+    // The returned file is to be released once the table is no longer borrowed
+    pub fn remove(&mut self, fd: u64) -> SysResult<Arc<OpenFile>> {
+        let index = ProcessFDTable::index(fd)?;
+        let descriptor = self
+            .slots
+            .get_mut(index)
+            .and_then(|slot| slot.take())
+            .ok_or(Errno::EBADF)?;
+        // Keep the table short once the last descriptors are closed
+        while let Some(None) = self.slots.last() {
+            self.slots.pop();
+        }
+        Ok(descriptor.file)
+    }
+
+    // The duplicate shares the open file, but not the close-on-exec flag
+    pub fn dup(&mut self, fd: u64) -> SysResult<u64> {
+        self.dup_from(fd, 0)
+    }
+
+    // Duplicate on the lowest free descriptor greater or equal to min
+    pub fn dup_from(&mut self, fd: u64, min: u64) -> SysResult<u64> {
+        let file = self.descriptor(fd)?.file.clone();
+        let index = self.lowest_free(ProcessFDTable::index(min).map_err(|_| Errno::EINVAL)?)?;
+        self.set(
+            index,
+            Descriptor {
+                file,
+                cloexec: false,
+            },
+        );
+        Ok(index as u64)
+    }
+
+    // Duplicate on new_fd, returning the file it replaced, to be released
+    pub fn dup2(&mut self, fd: u64, new_fd: u64) -> SysResult<(u64, Option<Arc<OpenFile>>)> {
+        let file = self.descriptor(fd)?.file.clone();
+        if fd == new_fd {
+            return Ok((new_fd, None));
+        }
+        let index = ProcessFDTable::index(new_fd)?;
+        let replaced = self.set(
+            index,
+            Descriptor {
+                file,
+                cloexec: false,
+            },
+        );
+        Ok((new_fd, replaced))
+    }
+
+    pub fn cloexec(&self, fd: u64) -> SysResult<bool> {
+        Ok(self.descriptor(fd)?.cloexec)
+    }
+
+    pub fn set_cloexec(&mut self, fd: u64, cloexec: bool) -> SysResult<()> {
+        let index = ProcessFDTable::index(fd)?;
+        match self.slots.get_mut(index) {
+            Some(Some(descriptor)) => {
+                descriptor.cloexec = cloexec;
+                Ok(())
+            }
+            _ => Err(Errno::EBADF),
+        }
+    }
+
+    fn take_all(&mut self) -> Vec<Arc<OpenFile>> {
+        self.slots
+            .drain(..)
+            .filter_map(|slot| slot.map(|d| d.file))
+            .collect()
+    }
+}
+
+// Descriptor table of a process, created with the console descriptors on first use
+pub async fn process_fd_table(thread: ThreadId) -> SysResult<ProcessFDTablet> {
+    if let Some(table) = PROCESS_FD_TABLES.lock().await.get(&thread) {
+        return Ok(table.clone());
+    }
+    // Opening the console locks the VFS, the tables are not locked meanwhile
+    let mut table = ProcessFDTable::with_console().await?;
+
+    let mut tables = PROCESS_FD_TABLES.lock().await;
+    // Another call created the table while the console was opened
+    if let Some(created) = tables.get(&thread).cloned() {
+        drop(tables);
+        for file in table.take_all() {
+            release(file).await;
+        }
+        return Ok(created);
+    }
+    let table = Arc::new(RefCell::new(table));
+    tables.insert(thread, table.clone());
+    Ok(table)
+}
+
+// Close every descriptor of an exiting process
+pub async fn release_process_fd_table(thread: ThreadId) {
+    let table = match PROCESS_FD_TABLES.lock().await.remove(&thread) {
+        Some(table) => table,
+        None => return,
+    };
+    let files = table.borrow_mut().take_all();
+    for file in files {
+        release(file).await;
+    }
+}
@@ -0,0 +1,95 @@
+use crate::drivers::block::{read_block, DeviceId, BLOCK_DEVICES, BLOCK_SIZE};
+use crate::fd::{seek_offset, FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{DirEntry, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::utils::AsyncMutex;
+
+use super::DevNode;
+
+use alloc::{boxed::Box, sync::Arc};
+use async_trait::async_trait;
+
+// Size in bytes of a registered block device, 0 if unknown
+pub async fn device_size(device: DeviceId) -> u64 {
+    let handle = match BLOCK_DEVICES.lock().await.get(device) {
+        Some(handle) => handle,
+        None => return 0,
+    };
+    let dev = handle.lock().await;
+    dev.block_count() * dev.block_size() as u64
+}
+
+// Raw access to the blocks of a device, the offset is a byte offset from block 0
+pub struct BlockFD {
+    pub fd: FDId,
+    offset: u64,
+    device: DeviceId,
+}
+
+impl BlockFD {
+    pub async fn new(device: DeviceId) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(BlockFD {
+            fd: id,
+            offset: 0,
+            device,
+        }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for BlockFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, _buf: &[u8], _count: usize) -> SysResult<usize> {
+        Err(Errno::EBADF)
+    }
+
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        let mut count = core::cmp::min(count, buf.len());
+        let size = device_size(self.device).await;
+        if size != 0 {
+            count = core::cmp::min(count as u64, size.saturating_sub(self.offset)) as usize;
+        }
+        let mut read: usize = 0;
+
+        while read < count {
+            let lba = (self.offset / BLOCK_SIZE as u64) as u32;
+            let block_offset = (self.offset % BLOCK_SIZE as u64) as usize;
+            let len = core::cmp::min(count - read, BLOCK_SIZE - block_offset);
+
+            let block = read_block(self.device, lba).await?;
+            buf[read..read + len].copy_from_slice(&block[block_offset..block_offset + len]);
+            read += len;
+            self.offset += len as u64;
+        }
+        Ok(read)
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    async fn lseek(&mut self, offset: i64, whence: u32) -> SysResult<u64> {
+        let size = device_size(self.device).await;
+        self.offset = seek_offset(self.offset, size, offset, whence)?;
+        Ok(self.offset)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        Err(Errno::ENOTDIR)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(DevNode::Block(self.device).stat().await)
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EINVAL)
+    }
+}
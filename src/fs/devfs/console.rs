@@ -0,0 +1,125 @@
+use crate::drivers::vga::WRITER;
+use crate::fd::{FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{DirEntry, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_RDONLY, O_WRONLY, POLLIN, POLLOUT};
+use crate::task::keyboard::{scancode_pending, ScancodeStream};
+use crate::utils::AsyncMutex;
+
+use super::DevNode;
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use async_trait::async_trait;
+use futures_util::{FutureExt, StreamExt};
+use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use x86_64::instructions::interrupts;
+
+// Output goes to the screen, input comes from the keyboard. Like /dev/kbd,
+// it takes the scancodes from the shared queue.
+pub struct ConsoleFD {
+    pub fd: FDId,
+    flags: u32,
+    scancodes: ScancodeStream,
+    keyboard: Keyboard<layouts::Us104Key, ScancodeSet1>,
+    pending: Vec<u8>, // Typed characters not read yet, in UTF-8
+}
+
+impl ConsoleFD {
+    pub async fn new(flags: u32) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(ConsoleFD {
+            fd: id,
+            flags,
+            scancodes: ScancodeStream::new(),
+            keyboard: Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore),
+            pending: Vec::new(),
+        }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+
+    // Keys without a character, and key releases, are dropped
+    fn decode(&mut self, scancode: u8) {
+        if let Ok(Some(key_event)) = self.keyboard.add_byte(scancode) {
+            if let Some(DecodedKey::Unicode(character)) = self.keyboard.process_keyevent(key_event)
+            {
+                let mut utf8 = [0; 4];
+                let encoded = character.encode_utf8(&mut utf8);
+                self.pending.extend_from_slice(encoded.as_bytes());
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for ConsoleFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, buf: &[u8], count: usize) -> SysResult<usize> {
+        if self.flags & O_ACCMODE == O_RDONLY {
+            return Err(Errno::EBADF);
+        }
+        let count = core::cmp::min(count, buf.len());
+        let text = String::from_utf8_lossy(&buf[..count]);
+        interrupts::without_interrupts(|| WRITER.lock().write_string(&text));
+        Ok(count)
+    }
+
+    // Waits for a first character, then returns the ones already typed
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        if self.flags & O_ACCMODE == O_WRONLY {
+            return Err(Errno::EBADF);
+        }
+        let count = core::cmp::min(count, buf.len());
+        let mut read: usize = 0;
+        while read < count {
+            if self.pending.is_empty() {
+                let scancode = match read {
+                    0 => self.scancodes.next().await,
+                    _ => self.scancodes.next().now_or_never().flatten(),
+                };
+                match scancode {
+                    Some(scancode) => self.decode(scancode),
+                    None => break,
+                }
+                continue;
+            }
+            let len = core::cmp::min(count - read, self.pending.len());
+            buf[read..read + len].copy_from_slice(&self.pending[..len]);
+            self.pending.drain(..len);
+            read += len;
+        }
+        Ok(read)
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    // Scancodes may not make a character, reading can still block
+    fn readiness(&self) -> u16 {
+        match !self.pending.is_empty() || scancode_pending() {
+            true => POLLIN | POLLOUT,
+            false => POLLOUT,
+        }
+    }
+
+    async fn lseek(&mut self, _offset: i64, _whence: u32) -> SysResult<u64> {
+        Err(Errno::ESPIPE)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        Err(Errno::ENOTDIR)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(DevNode::Console.stat().await)
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EINVAL)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::fd::{FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{DirEntry, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::POLLIN;
+use crate::task::keyboard::{scancode_pending, ScancodeStream};
+use crate::utils::AsyncMutex;
+
+use super::DevNode;
+
+use alloc::{boxed::Box, sync::Arc};
+use async_trait::async_trait;
+use futures_util::{FutureExt, StreamExt};
+
+// There is a single scancode queue, every open /dev/kbd takes from it
+pub struct KbdFD {
+    pub fd: FDId,
+    scancodes: ScancodeStream,
+}
+
+impl KbdFD {
+    pub async fn new() -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(KbdFD {
+            fd: id,
+            scancodes: ScancodeStream::new(),
+        }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for KbdFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, _buf: &[u8], _count: usize) -> SysResult<usize> {
+        Err(Errno::EBADF)
+    }
+
+    // Waits for a first scancode, then returns the ones already queued
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        let count = core::cmp::min(count, buf.len());
+        if count == 0 {
+            return Ok(0);
+        }
+
+        match self.scancodes.next().await {
+            Some(scancode) => buf[0] = scancode,
+            None => return Ok(0),
+        }
+
+        let mut read: usize = 1;
+        while read < count {
+            match self.scancodes.next().now_or_never() {
+                Some(Some(scancode)) => buf[read] = scancode,
+                _ => break,
+            }
+            read += 1;
+        }
+        Ok(read)
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    fn readiness(&self) -> u16 {
+        match scancode_pending() {
+            true => POLLIN,
+            false => 0,
+        }
+    }
+
+    async fn lseek(&mut self, _offset: i64, _whence: u32) -> SysResult<u64> {
+        Err(Errno::ESPIPE)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        Err(Errno::ENOTDIR)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(DevNode::Keyboard.stat().await)
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EINVAL)
+    }
+}
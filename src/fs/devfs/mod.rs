@@ -0,0 +1,193 @@
+mod block;
+mod console;
+mod kbd;
+mod serial;
+
+use crate::drivers::block::{DeviceId, BLOCK_DEVICES};
+use crate::fd::FDt;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_RDONLY, S_IFBLK, S_IFCHR, S_IFDIR};
+use crate::utils::AsyncMutex;
+
+use super::{path, DirEntry, FSt, FileSystem, FileSystemType, FileType, Stat};
+use block::BlockFD;
+use console::ConsoleFD;
+use kbd::KbdFD;
+use serial::SerialFD;
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use async_trait::async_trait;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DevNode {
+    Serial,
+    Console,
+    Keyboard,
+    Block(DeviceId), // Registered block device, named after it
+}
+
+const CHAR_NODES: [DevNode; 3] = [DevNode::Serial, DevNode::Console, DevNode::Keyboard];
+
+impl DevNode {
+    fn name(&self) -> &'static str {
+        match self {
+            DevNode::Serial => "ttyS0",
+            DevNode::Console => "console",
+            DevNode::Keyboard => "kbd",
+            DevNode::Block(_) => "",
+        }
+    }
+
+    fn file_type(&self) -> FileType {
+        match self {
+            DevNode::Block(_) => FileType::BlockDevice,
+            _ => FileType::CharDevice,
+        }
+    }
+
+    fn writable(&self) -> bool {
+        matches!(self, DevNode::Serial | DevNode::Console)
+    }
+
+    async fn stat(&self) -> Stat {
+        let (mode, size) = match self {
+            DevNode::Serial | DevNode::Console => (S_IFCHR | 0o620, 0),
+            DevNode::Keyboard => (S_IFCHR | 0o440, 0),
+            DevNode::Block(device) => (S_IFBLK | 0o440, block::device_size(*device).await),
+        };
+        Stat {
+            file_type: self.file_type(),
+            size,
+            block: 0,
+            mtime: 0,
+            mode,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    fn dir_entry(&self, name: &str) -> DirEntry {
+        DirEntry {
+            name: String::from(name),
+            file_type: self.file_type(),
+            size: 0,
+        }
+    }
+}
+
+pub struct DevFSType {}
+
+#[async_trait(?Send)]
+impl FileSystemType for DevFSType {
+    fn name(&self) -> &'static str {
+        "devfs"
+    }
+
+    // Not backed by any device
+    async fn mount(&self, _source: Option<DeviceId>) -> SysResult<FSt> {
+        Ok(Arc::new(AsyncMutex::new(DevFS::new())))
+    }
+}
+
+// Device nodes giving access to the drivers through file descriptors
+pub struct DevFS {}
+
+impl DevFS {
+    pub fn new() -> Self {
+        DevFS {}
+    }
+
+    async fn find(&self, name: &str) -> Option<DevNode> {
+        if let Some(node) = CHAR_NODES.iter().find(|node| node.name() == name) {
+            return Some(*node);
+        }
+        let device = BLOCK_DEVICES.lock().await.lookup(name)?;
+        Some(DevNode::Block(device))
+    }
+
+    // None for the root directory
+    async fn lookup(&self, path: &str) -> SysResult<Option<DevNode>> {
+        let components = path::normalize(path)?;
+        match components.as_slice() {
+            [] => Ok(None),
+            [name] => self.find(name).await.map(Some).ok_or(Errno::ENOENT),
+            // Device nodes are the only entries, under the root
+            [name, ..] => match self.find(name).await {
+                Some(_) => Err(Errno::ENOTDIR),
+                None => Err(Errno::ENOENT),
+            },
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl FileSystem for DevFS {
+    async fn open(&mut self, path: &str, flags: u32) -> SysResult<FDt> {
+        let node = self.lookup(path).await?.ok_or(Errno::EISDIR)?;
+        if !node.writable() && flags & O_ACCMODE != O_RDONLY {
+            return Err(Errno::EACCES);
+        }
+
+        let fd = match node {
+            DevNode::Serial => SerialFD::new(flags).await,
+            DevNode::Console => ConsoleFD::new(flags).await,
+            DevNode::Keyboard => KbdFD::new().await,
+            DevNode::Block(device) => BlockFD::new(device).await,
+        };
+        Ok(fd)
+    }
+
+    async fn readdir(&mut self, path: &str) -> SysResult<Vec<DirEntry>> {
+        if self.lookup(path).await?.is_some() {
+            return Err(Errno::ENOTDIR);
+        }
+        let mut entries = Vec::new();
+        for name in [".", ".."] {
+            entries.push(DirEntry {
+                name: String::from(name),
+                file_type: FileType::Directory,
+                size: 0,
+            });
+        }
+        entries.extend(CHAR_NODES.iter().map(|node| node.dir_entry(node.name())));
+        for (device, name) in BLOCK_DEVICES.lock().await.names() {
+            entries.push(DevNode::Block(device).dir_entry(&name));
+        }
+        Ok(entries)
+    }
+
+    async fn stat(&mut self, path: &str) -> SysResult<Stat> {
+        match self.lookup(path).await? {
+            Some(node) => Ok(node.stat().await),
+            None => Ok(Stat {
+                file_type: FileType::Directory,
+                size: 0,
+                block: 0,
+                mtime: 0,
+                mode: S_IFDIR | 0o755,
+                nlink: 2,
+                uid: 0,
+                gid: 0,
+            }),
+        }
+    }
+
+    async fn readlink(&mut self, path: &str) -> SysResult<String> {
+        self.lookup(path).await?;
+        Err(Errno::EINVAL)
+    }
+
+    // The nodes are fixed, or follow the registered devices
+    async fn mkdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EPERM)
+    }
+
+    async fn unlink(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EPERM)
+    }
+
+    async fn rmdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EPERM)
+    }
+}
@@ -0,0 +1,108 @@
+use crate::drivers::serial::SERIAL1;
+use crate::fd::{FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{DirEntry, Stat};
+use crate::interrupts::pic::pit;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_RDONLY, O_WRONLY, POLLIN, POLLOUT};
+use crate::utils::AsyncMutex;
+
+use super::DevNode;
+
+use alloc::{boxed::Box, sync::Arc};
+use async_trait::async_trait;
+use core::task::Poll;
+use futures_util::future::poll_fn;
+use x86_64::instructions::interrupts;
+
+pub struct SerialFD {
+    pub fd: FDId,
+    flags: u32,
+}
+
+impl SerialFD {
+    pub async fn new(flags: u32) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(SerialFD { fd: id, flags }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for SerialFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, buf: &[u8], count: usize) -> SysResult<usize> {
+        if self.flags & O_ACCMODE == O_RDONLY {
+            return Err(Errno::EBADF);
+        }
+        let count = core::cmp::min(count, buf.len());
+        Ok(interrupts::without_interrupts(|| {
+            SERIAL1.lock().write_bytes(&buf[..count])
+        }))
+    }
+
+    // Waits for a first byte, then returns the ones already received. The
+    // port raises no interrupt, it is checked again on every tick.
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        if self.flags & O_ACCMODE == O_WRONLY {
+            return Err(Errno::EBADF);
+        }
+        let count = core::cmp::min(count, buf.len());
+        if count == 0 {
+            return Ok(0);
+        }
+        poll_fn(|cx| {
+            if interrupts::without_interrupts(|| SERIAL1.lock().data_ready()) {
+                return Poll::Ready(());
+            }
+            pit::wake_on_tick(cx.waker());
+            Poll::Pending
+        })
+        .await;
+
+        Ok(interrupts::without_interrupts(|| {
+            let mut serial = SERIAL1.lock();
+            let mut read: usize = 0;
+            while read < count {
+                match serial.read_byte() {
+                    Some(byte) => buf[read] = byte,
+                    None => break,
+                }
+                read += 1;
+            }
+            read
+        }))
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    // Bytes are sent synchronously, writing never blocks
+    fn readiness(&self) -> u16 {
+        match interrupts::without_interrupts(|| SERIAL1.lock().data_ready()) {
+            true => POLLIN | POLLOUT,
+            false => POLLOUT,
+        }
+    }
+
+    async fn lseek(&mut self, _offset: i64, _whence: u32) -> SysResult<u64> {
+        Err(Errno::ESPIPE)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        Err(Errno::ENOTDIR)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(DevNode::Serial.stat().await)
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EINVAL)
+    }
+}
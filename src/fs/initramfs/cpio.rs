@@ -0,0 +1,79 @@
+// cpio "newc" format: a 110 bytes ASCII header, the file name and the file data,
+// both padded to 4 bytes, until the TRAILER!!! entry
+
+use crate::syscalls::io::{S_IFDIR, S_IFLNK, S_IFMT, S_IFREG};
+
+use super::{NodeKind, Record};
+
+use alloc::{string::String, vec::Vec};
+
+const MAGIC: &[u8] = b"070701";
+const MAGIC_CRC: &[u8] = b"070702"; // Same layout, with a checksum of the data
+const HEADER_SIZE: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+// Fields following the magic, as 8 hexadecimal digits each
+const FIELD_MODE: usize = 1;
+const FIELD_UID: usize = 2;
+const FIELD_GID: usize = 3;
+const FIELD_MTIME: usize = 5;
+const FIELD_FILESIZE: usize = 6;
+const FIELD_NAMESIZE: usize = 11;
+
+pub fn is_cpio(data: &[u8]) -> bool {
+    data.starts_with(MAGIC) || data.starts_with(MAGIC_CRC)
+}
+
+fn field(header: &[u8], index: usize) -> Option<u32> {
+    let start = MAGIC.len() + index * 8;
+    let digits = core::str::from_utf8(&header[start..start + 8]).ok()?;
+    u32::from_str_radix(digits, 16).ok()
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+pub fn parse(data: &'static [u8]) -> Option<Vec<Record>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let header = data.get(offset..offset + HEADER_SIZE)?;
+        if !is_cpio(header) {
+            return None;
+        }
+        let mode = field(header, FIELD_MODE)?;
+        let filesize = field(header, FIELD_FILESIZE)? as usize;
+        let namesize = field(header, FIELD_NAMESIZE)? as usize;
+
+        // The name size counts the terminating NUL byte
+        let name_start = offset + HEADER_SIZE;
+        let name = data.get(name_start..name_start + namesize)?;
+        let name = core::str::from_utf8(name).ok()?.trim_end_matches('\0');
+        if name == TRAILER {
+            break;
+        }
+
+        let data_start = align4(name_start + namesize);
+        let contents = data.get(data_start..data_start + filesize)?;
+        offset = align4(data_start + filesize);
+
+        let kind = match mode & S_IFMT {
+            S_IFREG => NodeKind::File(contents),
+            S_IFDIR => NodeKind::Directory(Default::default()),
+            S_IFLNK => NodeKind::Symlink(String::from_utf8_lossy(contents).into()),
+            // Device nodes and fifos have no meaning here
+            _ => continue,
+        };
+        records.push(Record {
+            path: String::from(name),
+            kind,
+            mode: mode & 0o7777,
+            uid: field(header, FIELD_UID)?,
+            gid: field(header, FIELD_GID)?,
+            mtime: field(header, FIELD_MTIME)? as i64,
+        });
+    }
+    Some(records)
+}
@@ -0,0 +1,87 @@
+use crate::fd::{seek_offset, FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{DirEntry, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::utils::AsyncMutex;
+
+use super::{InitNodet, NodeKind};
+
+use alloc::{boxed::Box, sync::Arc};
+use async_trait::async_trait;
+
+pub struct InitFD {
+    pub fd: FDId,
+    offset: usize, // Byte offset for files, entry index for directories
+    node: InitNodet,
+}
+
+impl InitFD {
+    pub async fn new(node: InitNodet) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(InitFD {
+            fd: id,
+            offset: 0,
+            node,
+        }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for InitFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, _buf: &[u8], _count: usize) -> SysResult<usize> {
+        Err(Errno::EBADF)
+    }
+
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        let data = match self.node.borrow().kind {
+            NodeKind::File(data) => data,
+            NodeKind::Directory(_) => return Err(Errno::EISDIR),
+            NodeKind::Symlink(_) => return Err(Errno::EINVAL),
+        };
+
+        if self.offset >= data.len() {
+            return Ok(0);
+        }
+        let count = core::cmp::min(core::cmp::min(count, buf.len()), data.len() - self.offset);
+        buf[..count].copy_from_slice(&data[self.offset..self.offset + count]);
+        self.offset += count;
+
+        Ok(count)
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    async fn lseek(&mut self, offset: i64, whence: u32) -> SysResult<u64> {
+        let size = self.node.borrow().size();
+        self.offset = seek_offset(self.offset as u64, size, offset, whence)? as usize;
+        Ok(self.offset as u64)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        let entry = match &self.node.borrow().kind {
+            NodeKind::Directory(children) => match children.iter().nth(self.offset) {
+                Some((name, child)) => child.borrow().dir_entry(name),
+                None => return Ok(None),
+            },
+            _ => return Err(Errno::ENOTDIR),
+        };
+        self.offset += 1;
+        Ok(Some(entry))
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(self.node.borrow().stat())
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+}
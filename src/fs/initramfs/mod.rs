@@ -0,0 +1,330 @@
+mod cpio;
+mod fd;
+mod tar;
+
+use crate::drivers::block::DeviceId;
+use crate::fd::FDt;
+use crate::println;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_RDONLY, S_IFDIR, S_IFLNK, S_IFREG};
+use crate::utils::AsyncMutex;
+
+use super::{path, DirEntry, FSt, FileSystem, FileSystemType, FileType, Stat};
+use fd::InitFD;
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use async_trait::async_trait;
+use core::cell::RefCell;
+use lazy_static::lazy_static;
+use multiboot2::BootInformation;
+use spin::Mutex;
+
+lazy_static! {
+    // Contents of the multiboot module, identity mapped and never freed
+    static ref INITRD: Mutex<Option<&'static [u8]>> = Mutex::new(None);
+}
+
+// Record the module loaded by the boot loader, once its frames are mapped
+pub fn init(boot_info: &BootInformation) {
+    // The multiboot2 crate only gives access to the first module tag
+    let module = match boot_info.module_tag() {
+        Some(module) => module,
+        None => return,
+    };
+    let start = module.start_address() as usize;
+    let end = module.end_address() as usize;
+    println!(
+        "Found module {} at {:#x}-{:#x}",
+        module.name().trim_end_matches('\0'),
+        start,
+        end
+    );
+    *INITRD.lock() = Some(unsafe { core::slice::from_raw_parts(start as *const u8, end - start) });
+}
+
+pub enum NodeKind {
+    File(&'static [u8]),
+    Directory(BTreeMap<String, InitNodet>),
+    Symlink(String),
+}
+
+pub type InitNodet = Arc<RefCell<InitNode>>;
+
+pub struct InitNode {
+    pub kind: NodeKind,
+    pub mode: u32, // Permission bits only
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: i64,
+}
+
+// Archive member, as read from either format
+pub struct Record {
+    pub path: String,
+    pub kind: NodeKind,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: i64,
+}
+
+impl InitNode {
+    fn new_dir() -> InitNodet {
+        Arc::new(RefCell::new(InitNode {
+            kind: NodeKind::Directory(BTreeMap::new()),
+            mode: 0o755,
+            uid: 0,
+            gid: 0,
+            mtime: 0,
+        }))
+    }
+
+    pub fn file_type(&self) -> FileType {
+        match self.kind {
+            NodeKind::File(_) => FileType::File,
+            NodeKind::Directory(_) => FileType::Directory,
+            NodeKind::Symlink(_) => FileType::Symlink,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        match &self.kind {
+            NodeKind::File(data) => data.len() as u64,
+            NodeKind::Directory(children) => children.len() as u64,
+            NodeKind::Symlink(target) => target.len() as u64,
+        }
+    }
+
+    pub fn dir_entry(&self, name: &str) -> DirEntry {
+        DirEntry {
+            name: String::from(name),
+            file_type: self.file_type(),
+            size: self.size(),
+        }
+    }
+
+    pub fn stat(&self) -> Stat {
+        let (file_mode, nlink) = match &self.kind {
+            NodeKind::File(_) => (S_IFREG, 1),
+            NodeKind::Symlink(_) => (S_IFLNK, 1),
+            NodeKind::Directory(children) => {
+                let subdirs = children
+                    .values()
+                    .filter(|c| c.borrow().file_type() == FileType::Directory)
+                    .count();
+                (S_IFDIR, 2 + subdirs as u32)
+            }
+        };
+        Stat {
+            file_type: self.file_type(),
+            size: self.size(),
+            block: 0,
+            mtime: self.mtime,
+            mode: file_mode | self.mode,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+        }
+    }
+}
+
+pub struct InitFSType {}
+
+#[async_trait(?Send)]
+impl FileSystemType for InitFSType {
+    fn name(&self) -> &'static str {
+        "initramfs"
+    }
+
+    // Read from the multiboot module rather than a block device
+    async fn mount(&self, _source: Option<DeviceId>) -> SysResult<FSt> {
+        let data = (*INITRD.lock()).ok_or(Errno::ENODEV)?;
+        let fs = InitFS::parse(data).ok_or(Errno::EINVAL)?;
+        Ok(Arc::new(AsyncMutex::new(fs)))
+    }
+}
+
+// Read only file system unpacked from a cpio (newc) or ustar archive
+pub struct InitFS {
+    root: InitNodet,
+}
+
+impl InitFS {
+    fn parse(data: &'static [u8]) -> Option<Self> {
+        let records = if cpio::is_cpio(data) {
+            cpio::parse(data)?
+        } else if tar::is_tar(data) {
+            tar::parse(data)?
+        } else {
+            println!("Unknown initramfs format");
+            return None;
+        };
+
+        let fs = InitFS {
+            root: InitNode::new_dir(),
+        };
+        for record in records {
+            fs.insert(record);
+        }
+        Some(fs)
+    }
+
+    // Missing parent directories are created, as archives may omit them.
+    // Names are relative to the archive root, as "./etc/motd" or "etc/motd".
+    fn insert(&self, record: Record) {
+        let mut components = match path::normalize(&format!("/{}", record.path)) {
+            Ok(components) => components,
+            Err(err) => {
+                println!("Ignoring initramfs entry {}: {}", record.path, err);
+                return;
+            }
+        };
+        let name = match components.pop() {
+            Some(name) => name,
+            None => {
+                // The archive root entry only carries the root attributes
+                let mut root = self.root.borrow_mut();
+                root.mode = record.mode;
+                root.uid = record.uid;
+                root.gid = record.gid;
+                root.mtime = record.mtime;
+                return;
+            }
+        };
+
+        let mut dir = self.root.clone();
+        for component in components {
+            let child = match &mut dir.borrow_mut().kind {
+                NodeKind::Directory(children) => children
+                    .entry(component)
+                    .or_insert_with(InitNode::new_dir)
+                    .clone(),
+                _ => {
+                    println!(
+                        "Ignoring initramfs entry {}: {}",
+                        record.path,
+                        Errno::ENOTDIR
+                    );
+                    return;
+                }
+            };
+            dir = child;
+        }
+
+        let mut dir = dir.borrow_mut();
+        let children = match &mut dir.kind {
+            NodeKind::Directory(children) => children,
+            _ => {
+                println!(
+                    "Ignoring initramfs entry {}: {}",
+                    record.path,
+                    Errno::ENOTDIR
+                );
+                return;
+            }
+        };
+        // A directory created implicitly keeps its children
+        if let Some(existing) = children.get(&name) {
+            let mut existing = existing.borrow_mut();
+            if let (NodeKind::Directory(_), NodeKind::Directory(_)) = (&existing.kind, &record.kind)
+            {
+                existing.mode = record.mode;
+                existing.uid = record.uid;
+                existing.gid = record.gid;
+                existing.mtime = record.mtime;
+                return;
+            }
+        }
+        children.insert(
+            name,
+            Arc::new(RefCell::new(InitNode {
+                kind: record.kind,
+                mode: record.mode,
+                uid: record.uid,
+                gid: record.gid,
+                mtime: record.mtime,
+            })),
+        );
+    }
+
+    fn lookup(&self, path: &str) -> SysResult<InitNodet> {
+        let components = path::normalize(path)?;
+        let mut node = self.root.clone();
+        for component in components {
+            let child = match &node.borrow().kind {
+                NodeKind::Directory(children) => {
+                    children.get(&component).ok_or(Errno::ENOENT)?.clone()
+                }
+                _ => return Err(Errno::ENOTDIR),
+            };
+            node = child;
+        }
+        Ok(node)
+    }
+}
+
+#[async_trait(?Send)]
+impl FileSystem for InitFS {
+    async fn open(&mut self, path: &str, flags: u32) -> SysResult<FDt> {
+        let node = self.lookup(path)?;
+        if flags & O_ACCMODE != O_RDONLY {
+            return Err(Errno::EROFS);
+        }
+        Ok(InitFD::new(node).await)
+    }
+
+    async fn readdir(&mut self, path: &str) -> SysResult<Vec<DirEntry>> {
+        let node = self.lookup(path)?;
+        let node = node.borrow();
+        let children = match &node.kind {
+            NodeKind::Directory(children) => children,
+            _ => return Err(Errno::ENOTDIR),
+        };
+
+        let mut entries = Vec::new();
+        entries.push(node.dir_entry("."));
+        entries.push(DirEntry {
+            name: "..".to_string(),
+            file_type: FileType::Directory,
+            size: 0,
+        });
+        for (name, child) in children {
+            entries.push(child.borrow().dir_entry(name));
+        }
+        Ok(entries)
+    }
+
+    async fn stat(&mut self, path: &str) -> SysResult<Stat> {
+        let node = self.lookup(path)?;
+        let stat = node.borrow().stat();
+        Ok(stat)
+    }
+
+    async fn readlink(&mut self, path: &str) -> SysResult<String> {
+        let node = self.lookup(path)?;
+        let target = match &node.borrow().kind {
+            NodeKind::Symlink(target) => target.clone(),
+            _ => return Err(Errno::EINVAL),
+        };
+        Ok(target)
+    }
+
+    async fn mkdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+
+    async fn unlink(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+
+    async fn rmdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+}
@@ -0,0 +1,97 @@
+// POSIX ustar format: 512 bytes headers with octal ASCII fields, each followed
+// by the file data padded to 512 bytes, until two zero filled blocks
+
+use super::{NodeKind, Record};
+
+use alloc::{format, string::String, vec::Vec};
+
+const BLOCK_SIZE: usize = 512;
+const MAGIC: &[u8] = b"ustar";
+
+// Offset and length of the header fields
+const NAME: (usize, usize) = (0, 100);
+const MODE: (usize, usize) = (100, 8);
+const UID: (usize, usize) = (108, 8);
+const GID: (usize, usize) = (116, 8);
+const SIZE: (usize, usize) = (124, 12);
+const MTIME: (usize, usize) = (136, 12);
+const TYPEFLAG: usize = 156;
+const LINKNAME: (usize, usize) = (157, 100);
+const MAGIC_FIELD: (usize, usize) = (257, 6);
+const PREFIX: (usize, usize) = (345, 155);
+
+// Type flags
+const REGTYPE: u8 = b'0';
+const AREGTYPE: u8 = b'\0'; // Regular file, pre POSIX archives
+const SYMTYPE: u8 = b'2';
+const DIRTYPE: u8 = b'5';
+
+pub fn is_tar(data: &[u8]) -> bool {
+    data.len() >= BLOCK_SIZE && data[MAGIC_FIELD.0..].starts_with(MAGIC)
+}
+
+fn string(header: &[u8], (start, len): (usize, usize)) -> String {
+    let field = &header[start..start + len];
+    let end = field.iter().position(|&c| c == 0).unwrap_or(len);
+    String::from_utf8_lossy(&field[..end]).into()
+}
+
+// Octal digits, padded with spaces or NUL bytes
+fn number(header: &[u8], field: (usize, usize)) -> Option<u64> {
+    let digits = string(header, field);
+    let digits = digits.trim_matches(|c| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+fn align_block(offset: usize) -> usize {
+    (offset + BLOCK_SIZE - 1) & !(BLOCK_SIZE - 1)
+}
+
+pub fn parse(data: &'static [u8]) -> Option<Vec<Record>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while let Some(header) = data.get(offset..offset + BLOCK_SIZE) {
+        // End of archive
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        if !is_tar(header) {
+            return None;
+        }
+
+        let size = number(header, SIZE)? as usize;
+        let data_start = offset + BLOCK_SIZE;
+        let contents = data.get(data_start..data_start + size)?;
+        offset = align_block(data_start + size);
+
+        let kind = match header[TYPEFLAG] {
+            REGTYPE | AREGTYPE => NodeKind::File(contents),
+            DIRTYPE => NodeKind::Directory(Default::default()),
+            SYMTYPE => NodeKind::Symlink(string(header, LINKNAME)),
+            // Hard links, devices and fifos are not supported
+            _ => continue,
+        };
+
+        // Long names are split between the prefix and the name
+        let prefix = string(header, PREFIX);
+        let name = string(header, NAME);
+        let path = match prefix.is_empty() {
+            true => name,
+            false => format!("{}/{}", prefix, name),
+        };
+
+        records.push(Record {
+            path,
+            kind,
+            mode: number(header, MODE)? as u32 & 0o7777,
+            uid: number(header, UID)? as u32,
+            gid: number(header, GID)? as u32,
+            mtime: number(header, MTIME)? as i64,
+        });
+    }
+    Some(records)
+}
@@ -0,0 +1,344 @@
+use crate::drivers::block::{read_block, DeviceId};
+use crate::fd::{seek_offset, FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{path, DirEntry, FSt, FileSystem, FileSystemType, FileType, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_RDONLY, S_IFDIR, S_IFREG};
+use crate::utils::AsyncMutex;
+
+use super::el_torito::{read_boot_catalog, BootCatalog, BootEntry};
+use super::iso9660::ISO_BLOCK_SIZE;
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use async_trait::async_trait;
+use core::fmt::Write;
+
+// Text description of the catalog, along with the images
+const CATALOG_NAME: &str = "catalog";
+
+pub struct BootFSType {}
+
+#[async_trait(?Send)]
+impl FileSystemType for BootFSType {
+    fn name(&self) -> &'static str {
+        "eltorito"
+    }
+
+    async fn mount(&self, source: Option<DeviceId>) -> SysResult<FSt> {
+        let device = source.ok_or(Errno::EINVAL)?;
+        let catalog = read_boot_catalog(device).await?;
+        Ok(Arc::new(AsyncMutex::new(BootFS { device, catalog })))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BootNode {
+    Catalog,
+    Image(BootEntry),
+}
+
+// Boot images of the El Torito catalog, as read only files named after
+// their index in the catalog and their platform
+pub struct BootFS {
+    device: DeviceId,
+    catalog: BootCatalog,
+}
+
+impl BootFS {
+    fn image_name(index: usize, entry: &BootEntry) -> String {
+        format!("{}-{}.img", index, entry.platform.name())
+    }
+
+    // Writing to a String never fails
+    fn catalog_text(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "id\t{}", self.catalog.id).unwrap();
+        writeln!(out, "block\t{}", self.catalog.catalog_lba).unwrap();
+        writeln!(
+            out,
+            "image\tbootable\temulation\tsegment\tsystem\tsectors\tblock"
+        )
+        .unwrap();
+        for (i, entry) in self.catalog.entries.iter().enumerate() {
+            writeln!(
+                out,
+                "{}\t{}\t{:?}\t{:#x}\t{:#x}\t{}\t{}",
+                BootFS::image_name(i, entry),
+                entry.bootable,
+                entry.emulation,
+                entry.load_segment,
+                entry.system_type,
+                entry.sector_count,
+                entry.image_lba
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    fn find(&self, name: &str) -> Option<BootNode> {
+        if name == CATALOG_NAME {
+            return Some(BootNode::Catalog);
+        }
+        self.catalog
+            .entries
+            .iter()
+            .enumerate()
+            .find(|(i, entry)| BootFS::image_name(*i, entry) == name)
+            .map(|(_, entry)| BootNode::Image(*entry))
+    }
+
+    // None for the root directory
+    fn lookup(&self, path: &str) -> SysResult<Option<BootNode>> {
+        let components = path::normalize(path)?;
+        match components.as_slice() {
+            [] => Ok(None),
+            [name] => self.find(name).map(Some).ok_or(Errno::ENOENT),
+            // Boot images are the only entries, under the root
+            [name, ..] => match self.find(name) {
+                Some(_) => Err(Errno::ENOTDIR),
+                None => Err(Errno::ENOENT),
+            },
+        }
+    }
+
+    fn image_stat(entry: &BootEntry) -> Stat {
+        BootFS::file_stat(entry.image_size(), entry.image_lba)
+    }
+
+    fn file_stat(size: u64, block: u32) -> Stat {
+        Stat {
+            file_type: FileType::File,
+            size,
+            block: block as u64,
+            mtime: 0,
+            mode: S_IFREG | 0o444,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl FileSystem for BootFS {
+    async fn open(&mut self, path: &str, flags: u32) -> SysResult<FDt> {
+        let node = self.lookup(path)?.ok_or(Errno::EISDIR)?;
+        if flags & O_ACCMODE != O_RDONLY {
+            return Err(Errno::EROFS);
+        }
+        let fd = match node {
+            BootNode::Catalog => {
+                let text = self.catalog_text();
+                BootCatalogFD::new(text, self.catalog.catalog_lba).await
+            }
+            BootNode::Image(entry) => BootImageFD::new(self.device, entry).await,
+        };
+        Ok(fd)
+    }
+
+    async fn readdir(&mut self, path: &str) -> SysResult<Vec<DirEntry>> {
+        if self.lookup(path)?.is_some() {
+            return Err(Errno::ENOTDIR);
+        }
+        let mut entries = Vec::new();
+        for name in [".", ".."] {
+            entries.push(DirEntry {
+                name: String::from(name),
+                file_type: FileType::Directory,
+                size: 0,
+            });
+        }
+        entries.push(DirEntry {
+            name: String::from(CATALOG_NAME),
+            file_type: FileType::File,
+            size: self.catalog_text().len() as u64,
+        });
+        for (i, entry) in self.catalog.entries.iter().enumerate() {
+            entries.push(DirEntry {
+                name: BootFS::image_name(i, entry),
+                file_type: FileType::File,
+                size: entry.image_size(),
+            });
+        }
+        Ok(entries)
+    }
+
+    async fn stat(&mut self, path: &str) -> SysResult<Stat> {
+        match self.lookup(path)? {
+            Some(BootNode::Catalog) => Ok(BootFS::file_stat(
+                self.catalog_text().len() as u64,
+                self.catalog.catalog_lba,
+            )),
+            Some(BootNode::Image(entry)) => Ok(BootFS::image_stat(&entry)),
+            None => Ok(Stat {
+                file_type: FileType::Directory,
+                size: self.catalog.entries.len() as u64 + 1,
+                block: self.catalog.catalog_lba as u64,
+                mtime: 0,
+                mode: S_IFDIR | 0o555,
+                nlink: 2,
+                uid: 0,
+                gid: 0,
+            }),
+        }
+    }
+
+    async fn readlink(&mut self, path: &str) -> SysResult<String> {
+        self.lookup(path)?;
+        Err(Errno::EINVAL)
+    }
+
+    async fn mkdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+
+    async fn unlink(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+
+    async fn rmdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+}
+
+pub struct BootImageFD {
+    pub fd: FDId,
+    offset: u64,
+    device: DeviceId,
+    entry: BootEntry,
+}
+
+impl BootImageFD {
+    pub async fn new(device: DeviceId, entry: BootEntry) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(BootImageFD {
+            fd: id,
+            offset: 0,
+            device,
+            entry,
+        }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for BootImageFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, _buf: &[u8], _count: usize) -> SysResult<usize> {
+        Err(Errno::EBADF)
+    }
+
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        let size = self.entry.image_size();
+        let count = core::cmp::min(count, buf.len());
+        let mut read: usize = 0;
+
+        while read < count && self.offset < size {
+            let block_offset = (self.offset % ISO_BLOCK_SIZE as u64) as usize;
+            let len = core::cmp::min(
+                core::cmp::min(count - read, ISO_BLOCK_SIZE as usize - block_offset),
+                (size - self.offset) as usize,
+            );
+
+            let lba = self.entry.image_lba + (self.offset / ISO_BLOCK_SIZE as u64) as u32;
+            let block = read_block(self.device, lba).await?;
+            buf[read..read + len].copy_from_slice(&block[block_offset..block_offset + len]);
+            read += len;
+            self.offset += len as u64;
+        }
+        Ok(read)
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    async fn lseek(&mut self, offset: i64, whence: u32) -> SysResult<u64> {
+        self.offset = seek_offset(self.offset, self.entry.image_size(), offset, whence)?;
+        Ok(self.offset)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        Err(Errno::ENOTDIR)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(BootFS::image_stat(&self.entry))
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+}
+
+// The catalog description, generated when opened
+pub struct BootCatalogFD {
+    pub fd: FDId,
+    offset: usize,
+    text: String,
+    block: u32, // Of the catalog
+}
+
+impl BootCatalogFD {
+    pub async fn new(text: String, block: u32) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(BootCatalogFD {
+            fd: id,
+            offset: 0,
+            text,
+            block,
+        }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for BootCatalogFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, _buf: &[u8], _count: usize) -> SysResult<usize> {
+        Err(Errno::EBADF)
+    }
+
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        let text = self.text.as_bytes();
+        if self.offset >= text.len() {
+            return Ok(0);
+        }
+        let count = core::cmp::min(core::cmp::min(count, buf.len()), text.len() - self.offset);
+        buf[..count].copy_from_slice(&text[self.offset..self.offset + count]);
+        self.offset += count;
+        Ok(count)
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    async fn lseek(&mut self, offset: i64, whence: u32) -> SysResult<u64> {
+        let size = self.text.len() as u64;
+        self.offset = seek_offset(self.offset as u64, size, offset, whence)? as usize;
+        Ok(self.offset as u64)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        Err(Errno::ENOTDIR)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(BootFS::file_stat(self.text.len() as u64, self.block))
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+}
@@ -0,0 +1,222 @@
+// El Torito bootable CD specification: the boot record volume descriptor points to
+// a boot catalog, listing the boot images stored on the disc
+
+use crate::drivers::block::{read_block, DeviceId};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::utils::unserialize;
+
+use super::read_vol_desc_set;
+
+use alloc::{string::String, vec::Vec};
+
+pub const EL_TORITO_IDF: &[u8] = b"EL TORITO SPECIFICATION";
+
+const CATALOG_ENTRY_SIZE: usize = 32;
+const VIRTUAL_SECTOR_SIZE: u64 = 512; // Unit of the sector counts
+
+// Header indicators
+const VALIDATION_HEADER: u8 = 0x1;
+const SECTION_HEADER_MORE: u8 = 0x90;
+const SECTION_HEADER_FINAL: u8 = 0x91;
+const EXTENSION_ENTRY: u8 = 0x44;
+
+const BOOTABLE: u8 = 0x88;
+const EMULATION_MASK: u8 = 0xf;
+
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+struct ValidationEntry {
+    header_id: u8,   // Always 1
+    platform_id: u8, // Platform of the default entry
+    _reserved: u16,
+    id_string: [u8; 24], // Manufacturer of the disc
+    checksum: u16,       // The 16 bit words of the entry sum up to 0
+    key: [u8; 2],        // 0x55, 0xaa
+}
+
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+struct SectionHeader {
+    header_indicator: u8, // 0x90, or 0x91 for the last section
+    platform_id: u8,
+    entry_count: u16, // Number of section entries following the header
+    id_string: [u8; 28],
+}
+
+// Layout of both the default entry and the section entries
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+struct CatalogEntry {
+    boot_indicator: u8, // 0x88 if bootable
+    media_type: u8,     // Emulation type in the low bits
+    load_segment: u16,  // 0 for the traditional 0x7c0
+    system_type: u8,    // Partition type of the emulated hard disk
+    _unused: u8,
+    sector_count: u16, // Virtual 512 bytes sectors loaded at boot
+    load_rba: u32,     // Image block index
+    selection_criteria: [u8; 20],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    X86,
+    PowerPC,
+    Mac,
+    Efi,
+    Other(u8),
+}
+
+impl Platform {
+    fn from_id(id: u8) -> Self {
+        match id {
+            0x0 => Platform::X86,
+            0x1 => Platform::PowerPC,
+            0x2 => Platform::Mac,
+            0xef => Platform::Efi,
+            id => Platform::Other(id),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::X86 => "x86",
+            Platform::PowerPC => "ppc",
+            Platform::Mac => "mac",
+            Platform::Efi => "efi",
+            Platform::Other(_) => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emulation {
+    None,
+    Floppy1200, // 1.2 MB diskette
+    Floppy1440, // 1.44 MB diskette
+    Floppy2880, // 2.88 MB diskette
+    HardDisk,
+    Other(u8),
+}
+
+impl Emulation {
+    fn from_media_type(media_type: u8) -> Self {
+        match media_type & EMULATION_MASK {
+            0 => Emulation::None,
+            1 => Emulation::Floppy1200,
+            2 => Emulation::Floppy1440,
+            3 => Emulation::Floppy2880,
+            4 => Emulation::HardDisk,
+            media => Emulation::Other(media),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BootEntry {
+    pub bootable: bool,
+    pub platform: Platform,
+    pub emulation: Emulation,
+    pub load_segment: u16,
+    pub system_type: u8,
+    pub sector_count: u16, // In virtual 512 bytes sectors
+    pub image_lba: u32,
+}
+
+impl BootEntry {
+    fn new(entry: &CatalogEntry, platform: Platform) -> Self {
+        BootEntry {
+            bootable: entry.boot_indicator == BOOTABLE,
+            platform,
+            emulation: Emulation::from_media_type(entry.media_type),
+            load_segment: entry.load_segment,
+            system_type: entry.system_type,
+            sector_count: entry.sector_count,
+            image_lba: entry.load_rba,
+        }
+    }
+
+    // Diskette images have a fixed size, other images are only known
+    // by the number of sectors loaded at boot
+    pub fn image_size(&self) -> u64 {
+        match self.emulation {
+            Emulation::Floppy1200 => 1200 * 1024,
+            Emulation::Floppy1440 => 1440 * 1024,
+            Emulation::Floppy2880 => 2880 * 1024,
+            _ => self.sector_count as u64 * VIRTUAL_SECTOR_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BootCatalog {
+    pub catalog_lba: u32,
+    pub id: String,              // Manufacturer, from the validation entry
+    pub entries: Vec<BootEntry>, // Default entry first, then the section entries
+}
+
+// Read the boot catalog referenced by the boot record volume descriptor
+pub async fn read_boot_catalog(device: DeviceId) -> SysResult<BootCatalog> {
+    let desc_set = read_vol_desc_set(device).await?;
+    let catalog_lba = desc_set.boot_catalog.ok_or(Errno::EINVAL)?;
+    // Only the first block is read, which holds up to 64 entries
+    let catalog = read_block(device, catalog_lba).await?;
+    parse_catalog(catalog_lba, &catalog).ok_or(Errno::EINVAL)
+}
+
+fn parse_catalog(catalog_lba: u32, catalog: &[u8]) -> Option<BootCatalog> {
+    let validation: &ValidationEntry = unserialize(catalog.as_ptr());
+    let checksum = catalog[..CATALOG_ENTRY_SIZE]
+        .chunks_exact(2)
+        .fold(0u16, |sum, w| {
+            sum.wrapping_add(u16::from_le_bytes([w[0], w[1]]))
+        });
+    if validation.header_id != VALIDATION_HEADER || validation.key != [0x55, 0xaa] || checksum != 0
+    {
+        return None;
+    }
+
+    let default: &CatalogEntry = unserialize(catalog[CATALOG_ENTRY_SIZE..].as_ptr());
+    let mut entries: Vec<BootEntry> = Vec::new();
+    entries.push(BootEntry::new(
+        default,
+        Platform::from_id(validation.platform_id),
+    ));
+
+    let mut offset = 2 * CATALOG_ENTRY_SIZE;
+    while offset + CATALOG_ENTRY_SIZE <= catalog.len() {
+        let header: &SectionHeader = unserialize(catalog[offset..].as_ptr());
+        let header_indicator = header.header_indicator;
+        if header_indicator != SECTION_HEADER_MORE && header_indicator != SECTION_HEADER_FINAL {
+            break;
+        }
+        let platform = Platform::from_id(header.platform_id);
+        offset += CATALOG_ENTRY_SIZE;
+
+        for _ in 0..header.entry_count {
+            if offset + CATALOG_ENTRY_SIZE > catalog.len() {
+                break;
+            }
+            let entry: &CatalogEntry = unserialize(catalog[offset..].as_ptr());
+            entries.push(BootEntry::new(entry, platform));
+            offset += CATALOG_ENTRY_SIZE;
+
+            // Extension entries only hold more selection criteria
+            while offset < catalog.len() && catalog[offset] == EXTENSION_ENTRY {
+                offset += CATALOG_ENTRY_SIZE;
+            }
+        }
+
+        if header_indicator == SECTION_HEADER_FINAL {
+            break;
+        }
+    }
+
+    let id_string = validation.id_string;
+    Some(BootCatalog {
+        catalog_lba,
+        id: String::from_utf8_lossy(&id_string)
+            .trim_end_matches(['\0', ' '])
+            .into(),
+        entries,
+    })
+}
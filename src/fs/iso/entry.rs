@@ -0,0 +1,231 @@
+use crate::drivers::block::{read_block, DeviceId};
+use crate::fs::{DirEntry, FileType, Stat};
+use crate::syscalls::errno::SysResult;
+use crate::syscalls::io::{S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFREG};
+
+use super::iso9660::{decode_joliet, IsoDir, IsoFileType, ISO_BLOCK_SIZE};
+use super::rock_ridge::RockRidge;
+use super::zisofs::Zisofs;
+
+use alloc::{string::String, vec, vec::Vec};
+use core::cmp::min;
+
+// Bound on the chain of continuation areas followed for one record
+const MAX_SUSP_CONTINUATIONS: usize = 16;
+
+// Contiguous part of a file, possibly interleaved with gaps
+#[derive(Debug, Clone, Copy)]
+pub struct Extent {
+    pub lba: u32,      // First data block, after the extended attribute record
+    pub size: u32,     // In bytes
+    pub unit_size: u8, // Blocks recorded between two gaps, 0 if not interleaved
+    pub gap_size: u8,  // Blocks skipped after each unit
+}
+
+impl Extent {
+    fn new(record: &IsoDir) -> Self {
+        Extent {
+            lba: record.data_blk.le + record.ext_size as u32,
+            size: record.file_size.le,
+            unit_size: record.unit_size,
+            gap_size: record.gap_size,
+        }
+    }
+
+    // Block holding the nth data block of the extent
+    fn block_lba(&self, block: u32) -> u32 {
+        if self.unit_size == 0 {
+            return self.lba + block;
+        }
+        let unit_size = self.unit_size as u32;
+        let unit = block / unit_size;
+        self.lba + unit * (unit_size + self.gap_size as u32) + block % unit_size
+    }
+}
+
+// Directory record along with its Rock Ridge attributes
+#[derive(Clone)]
+pub struct IsoEntry {
+    pub record: IsoDir,
+    pub rock_ridge: RockRidge,
+    pub joliet: bool, // Record from the Joliet directory tree
+    pub device: DeviceId,
+    pub extents: Vec<Extent>, // Several for multi-extent files, in file order
+    pub zisofs: Option<Zisofs>, // From the ZF entry or the header of the file
+}
+
+impl IsoEntry {
+    pub async fn new(device: DeviceId, record: &IsoDir, joliet: bool) -> SysResult<Self> {
+        // Rock Ridge only extends the primary directory tree
+        let rock_ridge = match joliet {
+            true => RockRidge::default(),
+            false => read_rock_ridge(device, record).await?,
+        };
+        let mut entry = IsoEntry {
+            record: *record,
+            rock_ridge,
+            joliet,
+            device,
+            extents: vec![Extent::new(record)],
+            zisofs: None,
+        };
+        // Detected now so that listings report the uncompressed size. A header
+        // that cannot be read leaves the file as is, reading it fails later.
+        if entry.file_type() == FileType::File {
+            entry.zisofs = Zisofs::detect(&entry).await.unwrap_or(None);
+        }
+        Ok(entry)
+    }
+
+    // Multi-extent files are recorded as consecutive records with the same name,
+    // all flagged MULTIDIR except the last one
+    pub fn is_complete(&self) -> bool {
+        !self.record.has_flag(IsoFileType::MULTIDIR)
+    }
+
+    pub fn add_extent(&mut self, record: &IsoDir) {
+        self.extents.push(Extent::new(record));
+        self.record.file_type = record.file_type;
+    }
+
+    pub fn size(&self) -> u64 {
+        self.extents.iter().map(|e| e.size as u64).sum()
+    }
+
+    // Uncompressed size of zisofs files
+    pub fn file_size(&self) -> u64 {
+        match self.zisofs {
+            Some(zisofs) => zisofs.size,
+            None => self.size(),
+        }
+    }
+
+    // Block holding the byte at offset in the file, the offset in this block,
+    // and the bytes stored contiguously from there, up to the end of the
+    // interleaving unit or of the extent
+    pub fn locate(&self, offset: u64) -> Option<(u32, u32, u64)> {
+        let mut extent_offset = offset;
+        for extent in self.extents.iter() {
+            if extent_offset < extent.size as u64 {
+                let block = (extent_offset / ISO_BLOCK_SIZE as u64) as u32;
+                let mut contiguous = extent.size as u64 - extent_offset;
+                if extent.unit_size != 0 {
+                    let unit_bytes = extent.unit_size as u64 * ISO_BLOCK_SIZE as u64;
+                    contiguous = min(contiguous, unit_bytes - extent_offset % unit_bytes);
+                }
+                return Some((
+                    extent.block_lba(block),
+                    (extent_offset % ISO_BLOCK_SIZE as u64) as u32,
+                    contiguous,
+                ));
+            }
+            extent_offset -= extent.size as u64;
+        }
+        None
+    }
+
+    // Entry of a record read from this directory
+    pub async fn child(&self, record: &IsoDir) -> SysResult<Self> {
+        IsoEntry::new(self.device, record, self.joliet).await
+    }
+
+    pub fn name(&self) -> String {
+        if let Some(name) = &self.rock_ridge.name {
+            return name.clone();
+        }
+        match self.record.get_idf() {
+            [0] => String::from("."),
+            [1] => String::from(".."),
+            // The version is stripped once decoded, ";1" bytes may belong to a UCS-2 character
+            _ if self.joliet => {
+                let name = decode_joliet(self.record.get_raw_idf());
+                match name.strip_suffix(";1") {
+                    Some(name) => String::from(name),
+                    None => name,
+                }
+            }
+            idf => String::from_utf8_lossy(idf).into_owned(),
+        }
+    }
+
+    // Rock Ridge and Joliet names are case sensitive, ISO9660 identifiers are upper case
+    pub fn matches(&self, path_component: &str) -> bool {
+        if self.rock_ridge.name.is_some() || self.joliet {
+            return self.name() == path_component;
+        }
+        self.record.matches(path_component.to_uppercase().as_str())
+    }
+
+    pub fn file_type(&self) -> FileType {
+        if let Some(mode) = self.rock_ridge.mode {
+            match mode & S_IFMT {
+                S_IFLNK => return FileType::Symlink,
+                S_IFDIR => return FileType::Directory,
+                S_IFREG => return FileType::File,
+                S_IFCHR => return FileType::CharDevice,
+                S_IFBLK => return FileType::BlockDevice,
+                S_IFIFO => return FileType::Fifo,
+                _ => {}
+            }
+        }
+        if self.rock_ridge.symlink.is_some() {
+            return FileType::Symlink;
+        }
+        match self.record.is_dir() {
+            true => FileType::Directory,
+            false => FileType::File,
+        }
+    }
+
+    pub fn dir_entry(&self) -> DirEntry {
+        DirEntry {
+            name: self.name(),
+            file_type: self.file_type(),
+            size: self.file_size(),
+        }
+    }
+
+    pub fn stat(&self) -> Stat {
+        let file_type = self.file_type();
+        let default_mode = match file_type {
+            FileType::Directory => S_IFDIR | 0o555,
+            FileType::Symlink => S_IFLNK | 0o777,
+            FileType::File => S_IFREG | 0o444,
+            FileType::CharDevice => S_IFCHR | 0o444,
+            FileType::BlockDevice => S_IFBLK | 0o444,
+            FileType::Fifo => S_IFIFO | 0o444,
+        };
+        Stat {
+            file_type,
+            size: self.file_size(),
+            block: self.record.data_blk.le as u64,
+            mtime: self.rock_ridge.mtime.unwrap_or(self.record.mtime()),
+            mode: self.rock_ridge.mode.unwrap_or(default_mode),
+            nlink: self.rock_ridge.nlink.unwrap_or(1),
+            uid: self.rock_ridge.uid.unwrap_or(0),
+            gid: self.rock_ridge.gid.unwrap_or(0),
+        }
+    }
+}
+
+async fn read_rock_ridge(device: DeviceId, record: &IsoDir) -> SysResult<RockRidge> {
+    let mut rock_ridge = RockRidge::default();
+    let mut continuation = rock_ridge.parse(record.system_use());
+
+    for _ in 0..MAX_SUSP_CONTINUATIONS {
+        let ce = match continuation {
+            Some(ce) => ce,
+            None => break,
+        };
+        let start = ce.offset as usize;
+        let end = core::cmp::min(start + ce.len as usize, ISO_BLOCK_SIZE as usize);
+        if start >= end {
+            break;
+        }
+
+        let block = read_block(device, ce.block).await?;
+        continuation = rock_ridge.parse(&block[start..end]);
+    }
+
+    Ok(rock_ridge)
+}
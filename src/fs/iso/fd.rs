@@ -1,78 +1,161 @@
-use crate::drivers::atapi::read_block;
-use crate::fd::{FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::drivers::block::{read_block, read_blocks};
+use crate::fd::{seek_offset, FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{DirEntry, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::utils::AsyncMutex;
 
+use super::entry::IsoEntry;
 use super::iso9660::{IsoDir, ISO_BLOCK_SIZE};
+use super::zisofs::ZisofsReader;
 
 use alloc::{boxed::Box, sync::Arc};
 use async_trait::async_trait;
-use core::cell::RefCell;
+use core::cmp::min;
 
 pub struct IsoFD {
     pub fd: FDId,
-    offset: u32,
-    lba: u32,
-    size: u32,
+    offset: u64,
+    size: u64, // Uncompressed size of zisofs files
+    entry: IsoEntry,
+    zisofs: Option<ZisofsReader>,
 }
 
 impl IsoFD {
-    pub async fn new(entry: &IsoDir) -> FDt {
-        let fd = Arc::new(RefCell::new(IsoFD {
-            fd: FDId::new(),
+    pub async fn new(entry: IsoEntry) -> SysResult<FDt> {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(IsoFD {
+            fd: id,
             offset: 0,
-            lba: entry.data_blk.le,
-            size: entry.file_size.le,
+            size: entry.file_size(),
+            zisofs: entry.zisofs.map(ZisofsReader::new),
+            entry,
         }));
 
-        FD_TABLE.lock().await.register_fd(fd.clone());
-        fd
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        Ok(fd)
     }
 }
 
-#[async_trait]
+#[async_trait(?Send)]
 impl FileDescriptor for IsoFD {
     fn get_fd(&self) -> FDId {
         self.fd
     }
 
-    async fn write(&mut self, _buf: &[u8], _count: usize) -> isize {
-        -1
+    async fn write(&mut self, _buf: &[u8], _count: usize) -> SysResult<usize> {
+        Err(Errno::EBADF)
     }
 
-    #[allow(unaligned_references)]
-    async fn read(&mut self, buf: &mut [u8], count: usize) -> isize {
-        let mut block_offset = self.offset / ISO_BLOCK_SIZE;
-        let mut content = read_block(self.lba + block_offset).await;
-        let mut read: isize = 0;
-        for _ in 0..count {
-            if self.offset >= self.size {
-                break;
-            }
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        let read = self.pread(buf, count, self.offset).await?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+
+    // zisofs files are decompressed as they are read
+    async fn pread(&mut self, buf: &mut [u8], count: usize, offset: u64) -> SysResult<usize> {
+        if self.entry.record.is_dir() {
+            return Err(Errno::EISDIR);
+        }
+        match &mut self.zisofs {
+            Some(zisofs) => zisofs.pread(&self.entry, buf, count, offset).await,
+            None => read_raw(&self.entry, buf, count, offset).await,
+        }
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    async fn lseek(&mut self, offset: i64, whence: u32) -> SysResult<u64> {
+        self.offset = seek_offset(self.offset, self.size, offset, whence)?;
+        Ok(self.offset)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        if !self.entry.record.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
 
-            buf[read as usize] = content[(self.offset % ISO_BLOCK_SIZE) as usize];
-            read += 1;
-            self.offset += 1;
+        let mut multi_extent: Option<IsoEntry> = None;
+        while self.offset < self.size {
+            let (lba, block_offset, _) = match self.entry.locate(self.offset) {
+                Some(location) => location,
+                None => break,
+            };
+            let block = read_block(self.entry.device, lba).await?;
+            // Zero padded end of block, records continue on the next one
+            if block[block_offset as usize] == 0 {
+                self.offset += (ISO_BLOCK_SIZE - block_offset) as u64;
+                continue;
+            }
+            let record = IsoDir::parse(&block[block_offset as usize..]).ok_or(Errno::EIO)?;
+            self.offset += record.dir_size as u64;
 
-            if self.offset % ISO_BLOCK_SIZE == 0 {
-                block_offset += 1;
-                content = read_block(self.lba + block_offset).await;
+            let entry = match multi_extent.take() {
+                Some(mut entry) => {
+                    entry.add_extent(record);
+                    entry
+                }
+                None => self.entry.child(record).await?,
+            };
+            match entry.is_complete() {
+                true => return Ok(Some(entry.dir_entry())),
+                false => multi_extent = Some(entry),
             }
         }
+        Ok(None)
+    }
 
-        read
+    async fn fstat(&self) -> SysResult<Stat> {
+        let mut stat = self.entry.stat();
+        stat.size = self.size;
+        Ok(stat)
     }
 
-    async fn close(&mut self) {
-        FD_TABLE.lock().await.unregister_fd(self);
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EROFS)
     }
+}
+
+// The extents and interleaving decide where each block is. Whole blocks stored
+// contiguously are read straight into the buffer, partial ones through the cache
+async fn read_raw(entry: &IsoEntry, buf: &mut [u8], count: usize, offset: u64) -> SysResult<usize> {
+    let size = entry.size();
+    let count = min(count, buf.len());
+    let mut offset = offset;
+    let mut read: usize = 0;
 
-    async fn lseek(&mut self, offset: i32, whence: u32) -> i32 {
-        use crate::syscalls::io::*;
-        match whence {
-            w if w == SEEK_SET => self.offset = offset as u32,
-            w if w == SEEK_CUR => self.offset = (self.offset as i32 + offset) as u32,
-            w if w == SEEK_END => self.offset = (self.size as i32 + offset) as u32,
-            _ => {}
+    while read < count && offset < size {
+        let (lba, block_offset, contiguous) = match entry.locate(offset) {
+            Some(location) => location,
+            None => break,
         };
-        self.offset as i32
+        let len = min(min((count - read) as u64, contiguous), size - offset) as usize;
+        let block_offset = block_offset as usize;
+
+        let len = if block_offset == 0 && len >= ISO_BLOCK_SIZE as usize {
+            let len = len - len % ISO_BLOCK_SIZE as usize;
+            read_blocks(entry.device, lba, &mut buf[read..read + len]).await?;
+            len
+        } else {
+            let len = min(len, ISO_BLOCK_SIZE as usize - block_offset);
+            let block = read_block(entry.device, lba).await?;
+            buf[read..read + len].copy_from_slice(&block[block_offset..block_offset + len]);
+            len
+        };
+        read += len;
+        offset += len as u64;
+    }
+
+    Ok(read)
+}
+
+// Read the stored data of the file until the buffer is full
+pub async fn read_exact(entry: &IsoEntry, buf: &mut [u8], offset: u64) -> SysResult<()> {
+    let len = buf.len();
+    match read_raw(entry, buf, len, offset).await? {
+        read if read == len => Ok(()),
+        _ => Err(Errno::EIO),
     }
 }
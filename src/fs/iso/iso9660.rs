@@ -1,3 +1,5 @@
+use alloc::string::String;
+
 pub const ISO_BLOCK_SIZE: u32 = 2048;
 
 // Twin values structs
@@ -20,15 +22,14 @@ pub struct MultiEndian16 {
 
 #[repr(C, packed)]
 #[derive(Copy, Clone)]
-struct IsoPathTable {
-    idf_len: u8,     // Identifier name length
-    ext_size: u8,    // Extended attribute record length
-    data_blk: u8,    // File data block index
-    parent_dir: u16, // Number of the parent dir
-    idf: [u8; 0],    // Directory name, of size Self::idf_len
+pub struct IsoPathTable {
+    pub idf_len: u8,     // Identifier name length
+    pub ext_size: u8,    // Extended attribute record length
+    pub data_blk: u32,   // Directory data block index
+    pub parent_dir: u16, // Number of the parent dir
+    pub idf: [u8; 0],    // Directory name, of size Self::idf_len
 }
 
-#[allow(dead_code)]
 impl IsoPathTable {
     #[allow(unaligned_references)]
     pub fn get_idf(&self) -> &[u8] {
@@ -40,7 +41,7 @@ impl IsoPathTable {
 
 #[allow(dead_code)]
 const ISO_MAX_DIR_DEPTH: usize = 8;
-const ISO_DATE_LEN: usize = 7;
+pub const ISO_DATE_LEN: usize = 7;
 
 #[repr(u8)]
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -62,7 +63,7 @@ pub struct IsoDir {
     pub data_blk: MultiEndian32,  // File data block index
     pub file_size: MultiEndian32, // File size
     pub date: [u8; ISO_DATE_LEN],
-    pub file_type: IsoFileType,
+    pub file_type: u8, // Bitmask of IsoFileType flags
 
     pub unit_size: u8,
     pub gap_size: u8,
@@ -74,6 +75,21 @@ pub struct IsoDir {
 }
 
 impl IsoDir {
+    // Record at the start of the buffer, which ends at most at the end of its
+    // sector. None unless the header and identifier fit in both.
+    pub fn parse(buf: &[u8]) -> Option<&IsoDir> {
+        let header_size = core::mem::size_of::<IsoDir>();
+        let dir_size = *buf.first()? as usize;
+        if dir_size < header_size || dir_size > buf.len() {
+            return None;
+        }
+        let record: &IsoDir = crate::utils::unserialize(buf.as_ptr());
+        if header_size + record.idf_len as usize > dir_size {
+            return None;
+        }
+        Some(record)
+    }
+
     #[allow(unaligned_references)]
     pub fn get_idf(&self) -> &[u8] {
         let mut len: usize = self.idf_len as usize;
@@ -87,19 +103,149 @@ impl IsoDir {
         }
     }
 
-    pub fn next_entry(&self) -> &IsoDir {
-        crate::utils::ref_raw_offset(self, self.dir_size as isize)
+    // Identifier as recorded, with its version
+    #[allow(unaligned_references)]
+    pub fn get_raw_idf(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.idf.as_ptr(), self.idf_len as usize) }
+    }
+
+    pub fn has_flag(&self, flag: IsoFileType) -> bool {
+        self.file_type & flag as u8 != 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.has_flag(IsoFileType::ISDIR)
     }
 
     pub fn matches(&self, path: &str) -> bool {
         self.get_idf().to_ascii_uppercase() == path.as_bytes()
     }
+
+    pub fn mtime(&self) -> i64 {
+        iso_date_to_unix(&self.date)
+    }
+
+    // System Use area, following the identifier and its padding byte
+    pub fn system_use(&self) -> &[u8] {
+        let header_size = core::mem::size_of::<IsoDir>();
+        let mut start = header_size + self.idf_len as usize;
+        if self.idf_len % 2 == 0 {
+            start += 1;
+        }
+        let end = self.dir_size as usize;
+        if start >= end {
+            return &[];
+        }
+        unsafe {
+            let record: *const u8 = (self as *const IsoDir).cast::<u8>();
+            core::slice::from_raw_parts(record.add(start), end - start)
+        }
+    }
+}
+
+// Joliet identifiers are UCS-2 big endian
+pub fn decode_joliet(idf: &[u8]) -> String {
+    let ucs2 = idf
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]));
+    core::char::decode_utf16(ucs2)
+        .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+        .collect()
 }
 
-// Primary volume descriptor structure
+// Recording date: years since 1900, month, day, hour, minute, second
+// and offset from GMT in 15 minutes intervals
+pub fn iso_date_to_unix(date: &[u8; ISO_DATE_LEN]) -> i64 {
+    // Date not specified
+    if date[1] == 0 {
+        return 0;
+    }
+
+    let days = days_from_civil(1900 + date[0] as i64, date[1] as i64, date[2] as i64);
+    let seconds = date[3] as i64 * 3600 + date[4] as i64 * 60 + date[5] as i64;
+    let gmt_offset = date[6] as i8 as i64 * 15 * 60;
+    days * 86400 + seconds - gmt_offset
+}
+
+// Volume descriptor date: "YYYYMMDDHHMMSScc" in ASCII digits
+// and offset from GMT in 15 minutes intervals
+pub fn iso_long_date_to_unix(date: &[u8; ISO_LDATE_LEN]) -> i64 {
+    let digits = |start: usize, len: usize| -> i64 {
+        date[start..start + len]
+            .iter()
+            .fold(0, |acc, d| acc * 10 + d.wrapping_sub(b'0') as i64 % 10)
+    };
+
+    // Date not specified
+    let month = digits(4, 2);
+    if month == 0 {
+        return 0;
+    }
+
+    let days = days_from_civil(digits(0, 4), month, digits(6, 2));
+    let seconds = digits(8, 2) * 3600 + digits(10, 2) * 60 + digits(12, 2);
+    let gmt_offset = date[16] as i8 as i64 * 15 * 60;
+    days * 86400 + seconds - gmt_offset
+}
+
+// Number of days between the Unix epoch and a date of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Iterator over the records of a directory extent
+pub struct IsoDirIter<'a> {
+    extent: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> IsoDirIter<'a> {
+    pub fn new(extent: &'a [u8]) -> Self {
+        IsoDirIter { extent, offset: 0 }
+    }
+}
+
+impl<'a> Iterator for IsoDirIter<'a> {
+    type Item = &'a IsoDir;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let block_size = ISO_BLOCK_SIZE as usize;
+        while self.offset < self.extent.len() {
+            let dir_size = self.extent[self.offset] as usize;
+            // Records never cross block boundaries, the end of a block is zero padded
+            if dir_size == 0 {
+                self.offset = (self.offset / block_size + 1) * block_size;
+                continue;
+            }
+            // Truncated or corrupt record
+            let sector_end = (self.offset / block_size + 1) * block_size;
+            let end = core::cmp::min(sector_end, self.extent.len());
+            let entry = IsoDir::parse(&self.extent[self.offset..end])?;
+            self.offset += dir_size;
+            return Some(entry);
+        }
+        None
+    }
+}
+
+// Volume descriptor structure
 
 pub const ISO_PRIM_VOLDESC_BLOCK: u32 = 16;
 
+// Volume descriptor types
+pub const ISO_VOLDESC_BOOT: u8 = 0;
+pub const ISO_VOLDESC_PRIM: u8 = 1;
+pub const ISO_VOLDESC_SUPP: u8 = 2;
+pub const ISO_VOLDESC_TERM: u8 = 255;
+
+// Joliet escape sequences, for UCS-2 levels 1, 2 and 3
+const JOLIET_ESCAPE_SEQUENCES: [&[u8]; 3] = [b"%/@", b"%/C", b"%/E"];
+
 const ISO_SYSIDF_LEN: usize = 32;
 const ISO_VOLIDF_LEN: usize = 32;
 const ISO_VOLSET_LEN: usize = 128;
@@ -109,14 +255,14 @@ const ISO_APP_LEN: usize = 128;
 const ISO_CPRFIL_LEN: usize = 37;
 const ISO_ABSFIL_LEN: usize = 37;
 const ISO_BIBFIL_LEN: usize = 37;
-const ISO_LDATE_LEN: usize = 17;
+pub const ISO_LDATE_LEN: usize = 17;
 
 #[repr(C, packed)]
 #[derive(Copy, Clone)]
 pub struct IsoPrimVolDesc {
-    pub vol_desc_type: u8,       // Volume descripto type (1)
+    pub vol_desc_type: u8, // Volume descripto type (1, or 2 for supplementary)
     pub std_identifier: [u8; 5], // standard identifier ("CD001")
-    pub vol_desc_version: u8,    // Volume descriptor version (1)
+    pub vol_desc_version: u8, // Volume descriptor version (1)
 
     pub _unused1: u8,
 
@@ -127,7 +273,7 @@ pub struct IsoPrimVolDesc {
 
     pub vol_blk_count: MultiEndian32, // Number of logical blocks in the volume
 
-    pub _unused3: [u8; 32],
+    pub escape_sequences: [u8; 32], // Supplementary descriptor character set (unused in primary)
 
     pub vol_set_size: MultiEndian16, // The Volume Set size of the volume
     pub vol_seq_num: MultiEndian16,  // The number of the volume in the set
@@ -158,3 +304,30 @@ pub struct IsoPrimVolDesc {
 
     pub file_struct_version: u8, // File structure version (1)
 }
+
+impl IsoPrimVolDesc {
+    pub fn is_joliet(&self) -> bool {
+        let escape_sequences = self.escape_sequences;
+        self.vol_desc_type == ISO_VOLDESC_SUPP
+            && JOLIET_ESCAPE_SEQUENCES
+                .iter()
+                .any(|seq| escape_sequences.starts_with(seq))
+    }
+}
+
+const ISO_BOOTSYSIDF_LEN: usize = 32;
+const ISO_BOOTIDF_LEN: usize = 32;
+
+// Boot record volume descriptor, El Torito uses it to locate its boot catalog
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct IsoBootRecordVolDesc {
+    pub vol_desc_type: u8,       // Volume descriptor type (0)
+    pub std_identifier: [u8; 5], // standard identifier ("CD001")
+    pub vol_desc_version: u8,    // Volume descriptor version (1)
+
+    pub boot_sys_idf: [u8; ISO_BOOTSYSIDF_LEN], // Boot system identifier
+    pub boot_idf: [u8; ISO_BOOTIDF_LEN],        // Boot identifier (unused by El Torito)
+
+    pub catalog_blk: u32, // El Torito boot catalog block index, little endian
+}
@@ -1,84 +1,392 @@
+mod boot_fs;
+pub mod el_torito;
+mod entry;
 mod fd;
 pub mod iso9660;
+mod path_table;
+mod rock_ridge;
+mod zisofs;
 
-use crate::drivers::atapi::read_block;
+use crate::drivers::block::{read_block, DeviceId};
 use crate::fd::FDt;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_RDONLY, O_TRUNC};
 use crate::utils::unserialize;
+use crate::utils::AsyncMutex;
 
-use super::FileSystem;
+use super::{DirEntry, FSt, FileSystem, FileSystemType, Stat};
+pub use boot_fs::BootFSType;
+use entry::IsoEntry;
 use fd::IsoFD;
-use iso9660::{IsoDir, IsoPrimVolDesc};
+use iso9660::{IsoBootRecordVolDesc, IsoDir, IsoDirIter, IsoPrimVolDesc};
+use path_table::{PathTable, PathTableNames, PATH_TABLE_ROOT};
 
-use alloc::{boxed::Box, string::String, vec::Vec};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
 use async_trait::async_trait;
 
-pub struct IsoFS {}
+// Upper bound on the volume descriptor set length
+const ISO_MAX_VOLDESC: u32 = 64;
+// Upper bound on the size of the directories and path tables read whole
+const ISO_MAX_EXTENT_SIZE: u32 = 256 * 1024;
+
+pub struct IsoFSType {}
+
+#[async_trait(?Send)]
+impl FileSystemType for IsoFSType {
+    fn name(&self) -> &'static str {
+        "iso9660"
+    }
+
+    async fn mount(&self, source: Option<DeviceId>) -> SysResult<FSt> {
+        let mut fs = IsoFS::new(source.ok_or(Errno::EINVAL)?);
+        // Fail now rather than on the first access when the source holds no ISO volume
+        fs.root().await?;
+        Ok(Arc::new(AsyncMutex::new(fs)))
+    }
+}
+
+pub struct IsoFS {
+    device: DeviceId,
+    root: Option<IsoEntry>, // Root of the directory tree paths are resolved in
+    path_table: Option<PathTable>,
+}
+
+impl IsoFS {
+    pub fn new(device: DeviceId) -> Self {
+        IsoFS {
+            device,
+            root: None,
+            path_table: None,
+        }
+    }
+
+    async fn root(&mut self) -> SysResult<IsoEntry> {
+        if self.root.is_none() {
+            self.mount().await?;
+        }
+        self.root.clone().ok_or(Errno::EINVAL)
+    }
+
+    // Select the directory tree and cache its path table
+    async fn mount(&mut self) -> SysResult<()> {
+        let (root, desc) = read_root(self.device).await?;
+        let names = if root.joliet {
+            PathTableNames::Joliet
+        } else if root.rock_ridge.is_present() {
+            PathTableNames::RockRidge
+        } else {
+            PathTableNames::Iso9660
+        };
+        self.path_table = read_path_table(self.device, &desc, names).await?;
+        self.root = Some(root);
+        Ok(())
+    }
+
+    // Find the directory record of a path relative to the ISO root
+    async fn lookup(&mut self, path: &str) -> SysResult<IsoEntry> {
+        let root: IsoEntry = self.root().await?;
+
+        let path_split: Vec<&str> = path.split("/").filter(|p| p != &"").collect();
+        let (last, dirs) = match path_split.split_last() {
+            Some(split) => split,
+            None => return Ok(root),
+        };
+
+        let dir: IsoEntry = match self.path_table {
+            Some(_) => self.walk_path_table(dirs, root.joliet).await?,
+            None => walk_dir_records(root, dirs).await?,
+        };
+
+        if !dir.record.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        find_entry(&dir, last).await?.ok_or(Errno::ENOENT)
+    }
+
+    // Jump to the directory through the path table, without reading the intermediate ones
+    async fn walk_path_table(&mut self, dirs: &[&str], joliet: bool) -> SysResult<IsoEntry> {
+        let mut dir: usize = PATH_TABLE_ROOT;
+
+        for path_component in dirs {
+            let path_table = self.path_table.as_ref().ok_or(Errno::EIO)?;
+            if !path_table.names_known(dir) {
+                self.read_path_table_names(dir, joliet).await?;
+            }
+            let path_table = self.path_table.as_ref().ok_or(Errno::EIO)?;
+            // The path table only lists directories, a file in the path is not found
+            dir = match path_table.find_child(dir, path_component) {
+                Some(child) => child,
+                None => return Err(self.missing_dir_error(dir, path_component, joliet).await),
+            };
+        }
+
+        let extent = self
+            .path_table
+            .as_ref()
+            .and_then(|t| t.get(dir))
+            .ok_or(Errno::EIO)?
+            .extent;
+        read_dot_entry(self.device, extent, joliet).await
+    }
+
+    // A component missing from the path table is either missing or a file
+    async fn missing_dir_error(&self, dir: usize, path_component: &str, joliet: bool) -> Errno {
+        let extent = match self.path_table.as_ref().and_then(|t| t.get(dir)) {
+            Some(path_table_dir) => path_table_dir.extent,
+            None => return Errno::EIO,
+        };
+        let dir_entry = match read_dot_entry(self.device, extent, joliet).await {
+            Ok(dir_entry) => dir_entry,
+            Err(err) => return err,
+        };
+        match find_entry(&dir_entry, path_component).await {
+            Ok(Some(_)) => Errno::ENOTDIR,
+            Ok(None) => Errno::ENOENT,
+            Err(err) => err,
+        }
+    }
+
+    // Rock Ridge names of the subdirectories are only stored in the directory records
+    async fn read_path_table_names(&mut self, dir: usize, joliet: bool) -> SysResult<()> {
+        let extent = match self.path_table.as_ref().and_then(|t| t.get(dir)) {
+            Some(path_table_dir) => path_table_dir.extent,
+            None => return Ok(()),
+        };
+        let dir_entry = read_dot_entry(self.device, extent, joliet).await?;
+        let records = read_extent(&dir_entry).await?;
+
+        for record in IsoDirIter::new(&records) {
+            if !record.is_dir() || matches!(record.get_idf(), [0] | [1]) {
+                continue;
+            }
+            let name = dir_entry.child(record).await?.name();
+            if let Some(path_table) = self.path_table.as_mut() {
+                path_table.set_name(dir, record.data_blk.le, name);
+            }
+        }
+        Ok(())
+    }
+}
 
 #[async_trait(?Send)]
-#[allow(unaligned_references)]
 impl FileSystem for IsoFS {
-    async fn open(&mut self, path: &str, flags: u32) -> Option<FDt> {
+    async fn open(&mut self, path: &str, flags: u32) -> SysResult<FDt> {
+        let entry: IsoEntry = self.lookup(path).await?;
+
         // ISO is a read only file system
-        if flags != crate::syscalls::io::O_RDONLY {
-            return None;
+        if flags & O_ACCMODE != O_RDONLY || flags & O_TRUNC != 0 {
+            return Err(Errno::EROFS);
         }
+        IsoFD::new(entry).await
+    }
 
-        let voldesc = get_prim_vol_desc().await;
+    async fn readdir(&mut self, path: &str) -> SysResult<Vec<DirEntry>> {
+        let dir: IsoEntry = self.lookup(path).await?;
+        if !dir.record.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
 
-        // Invalid ISO
-        if voldesc.std_identifier != "CD001".as_bytes() {
-            return None;
+        let extent = read_extent(&dir).await?;
+        let mut entries: Vec<DirEntry> = Vec::new();
+        let mut multi_extent: Option<IsoEntry> = None;
+        for record in IsoDirIter::new(&extent) {
+            let entry = match multi_extent.take() {
+                Some(mut entry) => {
+                    entry.add_extent(record);
+                    entry
+                }
+                None => dir.child(record).await?,
+            };
+            match entry.is_complete() {
+                true => entries.push(entry.dir_entry()),
+                false => multi_extent = Some(entry),
+            }
         }
+        Ok(entries)
+    }
 
-        let root: &IsoDir = &voldesc.root_dir;
-        let mut curr_entry_block: [u8; iso9660::ISO_BLOCK_SIZE as usize] =
-            read_block(root.data_blk.le).await;
-
-        let mut curr_entry: &IsoDir = unserialize(curr_entry_block.as_ptr());
-
-        let path_s: String = String::from(path);
-        let path_split: Vec<String> = path_s
-            .split("/")
-            .filter(|p| p != &"")
-            .map(|s| s.to_uppercase())
-            .collect();
-        let path_it = path_split.iter();
-
-        for path_component in path_it {
-            let mut found = false;
-            while curr_entry.idf_len != 0 {
-                // Found entry
-                if curr_entry.matches(path_component.as_str()) {
-                    found = true;
-
-                    // Not the last component, go 1 directory deeper
-                    if path_component.as_str() != path_split[path_split.len() - 1] {
-                        // Not a directory
-                        if curr_entry.file_type != iso9660::IsoFileType::ISDIR {
-                            return None;
-                        }
-                        // Deeper entries
-                        curr_entry_block = read_block(curr_entry.data_blk.le).await;
-                        curr_entry = unserialize(curr_entry_block.as_ptr());
+    async fn stat(&mut self, path: &str) -> SysResult<Stat> {
+        let entry: IsoEntry = self.lookup(path).await?;
+        Ok(entry.stat())
+    }
+
+    async fn readlink(&mut self, path: &str) -> SysResult<String> {
+        let entry: IsoEntry = self.lookup(path).await?;
+        entry.rock_ridge.symlink.ok_or(Errno::EINVAL)
+    }
+
+    async fn mkdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+
+    async fn unlink(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+
+    async fn rmdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EROFS)
+    }
+}
+
+// Walk down the directories one level at a time
+async fn walk_dir_records(root: IsoEntry, dirs: &[&str]) -> SysResult<IsoEntry> {
+    let mut curr_dir: IsoEntry = root;
+
+    for path_component in dirs {
+        if !curr_dir.record.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        curr_dir = find_entry(&curr_dir, path_component)
+            .await?
+            .ok_or(Errno::ENOENT)?;
+    }
+
+    Ok(curr_dir)
+}
+
+// Search a directory extent block by block for an entry,
+// gathering the following records of multi-extent files
+async fn find_entry(dir: &IsoEntry, path_component: &str) -> SysResult<Option<IsoEntry>> {
+    let size = dir.record.file_size.le;
+    let block_count = (size + iso9660::ISO_BLOCK_SIZE - 1) / iso9660::ISO_BLOCK_SIZE;
+    let mut found: Option<IsoEntry> = None;
+
+    for i in 0..block_count {
+        let block = read_block(dir.device, dir.record.data_blk.le + i).await?;
+        let len = core::cmp::min(size - i * iso9660::ISO_BLOCK_SIZE, iso9660::ISO_BLOCK_SIZE);
+
+        for record in IsoDirIter::new(&block[..len as usize]) {
+            match found.as_mut() {
+                Some(entry) => entry.add_extent(record),
+                None => {
+                    let entry = dir.child(record).await?;
+                    if !entry.matches(path_component) {
+                        continue;
                     }
-                    break;
+                    found = Some(entry);
                 }
-
-                // Next entry
-                curr_entry = curr_entry.next_entry();
             }
+            if found.as_ref().is_some_and(|entry| entry.is_complete()) {
+                return Ok(found);
+            }
+        }
+    }
+    // Truncated multi-extent file
+    Ok(found)
+}
+
+// Read every block of a file or directory extent
+async fn read_extent(entry: &IsoEntry) -> SysResult<Vec<u8>> {
+    read_blocks_bounded(
+        entry.device,
+        entry.record.data_blk.le,
+        entry.record.file_size.le,
+    )
+    .await
+}
+
+// Read size bytes from block blk on. The size comes from the disk, it is
+// checked before allocating the buffer.
+async fn read_blocks_bounded(device: DeviceId, blk: u32, size: u32) -> SysResult<Vec<u8>> {
+    if size > ISO_MAX_EXTENT_SIZE {
+        return Err(Errno::EIO);
+    }
+    let mut data: Vec<u8> = Vec::new();
+    data.try_reserve_exact(size as usize)
+        .map_err(|_| Errno::ENOMEM)?;
+
+    let block_count = (size + iso9660::ISO_BLOCK_SIZE - 1) / iso9660::ISO_BLOCK_SIZE;
+    for i in 0..block_count {
+        let block = read_block(device, blk.checked_add(i).ok_or(Errno::EIO)?).await?;
+        let len = core::cmp::min(block.len(), size as usize - data.len());
+        data.extend_from_slice(&block[..len]);
+    }
+    Ok(data)
+}
+
+pub struct IsoVolDescSet {
+    pub prim: IsoPrimVolDesc,
+    pub joliet: Option<IsoPrimVolDesc>,
+    pub boot_catalog: Option<u32>, // El Torito boot catalog block index
+}
 
-            // File not found
-            if !found {
-                return None;
+// Scan the volume descriptors, from block 16 up to the set terminator
+pub async fn read_vol_desc_set(device: DeviceId) -> SysResult<IsoVolDescSet> {
+    let mut prim: Option<IsoPrimVolDesc> = None;
+    let mut joliet: Option<IsoPrimVolDesc> = None;
+    let mut boot_catalog: Option<u32> = None;
+
+    for blk in iso9660::ISO_PRIM_VOLDESC_BLOCK..iso9660::ISO_PRIM_VOLDESC_BLOCK + ISO_MAX_VOLDESC {
+        let desc_block = read_block(device, blk).await?;
+        let desc: &IsoPrimVolDesc = unserialize(desc_block.as_ptr());
+
+        // Invalid ISO
+        if desc.std_identifier != "CD001".as_bytes() {
+            return Err(Errno::EINVAL);
+        }
+
+        match desc.vol_desc_type {
+            iso9660::ISO_VOLDESC_BOOT if boot_catalog.is_none() => {
+                let boot: &IsoBootRecordVolDesc = unserialize(desc_block.as_ptr());
+                if boot.boot_sys_idf.starts_with(el_torito::EL_TORITO_IDF) {
+                    boot_catalog = Some(boot.catalog_blk);
+                }
             }
+            iso9660::ISO_VOLDESC_PRIM if prim.is_none() => prim = Some(*desc),
+            iso9660::ISO_VOLDESC_SUPP if joliet.is_none() && desc.is_joliet() => {
+                joliet = Some(*desc)
+            }
+            iso9660::ISO_VOLDESC_TERM => break,
+            _ => {}
         }
+    }
+
+    Ok(IsoVolDescSet {
+        prim: prim.ok_or(Errno::EINVAL)?,
+        joliet,
+        boot_catalog,
+    })
+}
+
+// Root of the Rock Ridge or plain ISO9660 tree, or of the Joliet tree
+// when the primary tree has no Rock Ridge entries, with its volume descriptor
+async fn read_root(device: DeviceId) -> SysResult<(IsoEntry, IsoPrimVolDesc)> {
+    let desc_set = read_vol_desc_set(device).await?;
+
+    let prim_root = read_dot_entry(device, desc_set.prim.root_dir.data_blk.le, false).await?;
+    if prim_root.rock_ridge.is_present() {
+        return Ok((prim_root, desc_set.prim));
+    }
 
-        Some(IsoFD::new(curr_entry).await)
+    match desc_set.joliet {
+        Some(joliet) => {
+            let joliet_root = read_dot_entry(device, joliet.root_dir.data_blk.le, true).await?;
+            Ok((joliet_root, joliet))
+        }
+        None => Ok((prim_root, desc_set.prim)),
     }
 }
 
-pub async fn get_prim_vol_desc() -> IsoPrimVolDesc {
-    let desc_block = read_block(iso9660::ISO_PRIM_VOLDESC_BLOCK).await;
-    *unserialize::<IsoPrimVolDesc>(desc_block.as_ptr())
+// The "." record of a directory describes the directory itself,
+// along with its Rock Ridge attributes
+async fn read_dot_entry(device: DeviceId, extent: u32, joliet: bool) -> SysResult<IsoEntry> {
+    let block = read_block(device, extent).await?;
+    let record = IsoDir::parse(&block).ok_or(Errno::EIO)?;
+    IsoEntry::new(device, record, joliet).await
+}
+
+// Load the little endian path table
+async fn read_path_table(
+    device: DeviceId,
+    desc: &IsoPrimVolDesc,
+    names: PathTableNames,
+) -> SysResult<Option<PathTable>> {
+    let size = desc.path_table_size.le;
+    let table = read_blocks_bounded(device, desc.le_path_table_blk, size).await?;
+
+    let path_table = PathTable::parse(&table, names);
+    match path_table.is_empty() {
+        true => Ok(None),
+        false => Ok(Some(path_table)),
+    }
 }
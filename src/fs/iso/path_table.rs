@@ -0,0 +1,100 @@
+use crate::utils::unserialize;
+
+use super::iso9660::{decode_joliet, IsoPathTable};
+
+use alloc::{string::String, vec::Vec};
+
+pub const PATH_TABLE_ROOT: usize = 0; // Index of the root directory
+
+// Names the directories are looked up by
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PathTableNames {
+    Iso9660,   // Upper case identifiers of the path table
+    Joliet,    // UCS-2 identifiers of the Joliet path table
+    RockRidge, // NM names, only found in the parent directory records
+}
+
+#[derive(Clone)]
+pub struct PathTableDir {
+    pub extent: u32,          // Directory data block index
+    pub parent: usize,        // Index of the parent directory
+    pub name: Option<String>, // None until read from the parent directory records
+}
+
+// Every directory of the volume, in path table order
+#[derive(Clone)]
+pub struct PathTable {
+    dirs: Vec<PathTableDir>,
+    names: PathTableNames,
+}
+
+impl PathTable {
+    pub fn parse(table: &[u8], names: PathTableNames) -> Self {
+        let header_size = core::mem::size_of::<IsoPathTable>();
+        let mut dirs: Vec<PathTableDir> = Vec::new();
+        let mut offset: usize = 0;
+
+        while offset + header_size <= table.len() {
+            let record: &IsoPathTable = unserialize(table[offset..].as_ptr());
+            let idf_len = record.idf_len as usize;
+            if idf_len == 0 || offset + header_size + idf_len > table.len() {
+                break;
+            }
+
+            let name = match names {
+                PathTableNames::Iso9660 => {
+                    Some(String::from_utf8_lossy(record.get_idf()).into_owned())
+                }
+                PathTableNames::Joliet => Some(decode_joliet(record.get_idf())),
+                PathTableNames::RockRidge => None,
+            };
+            // Directory numbers start at 1
+            dirs.push(PathTableDir {
+                extent: record.data_blk,
+                parent: (record.parent_dir as usize).saturating_sub(1),
+                name,
+            });
+
+            // Identifiers are padded to an even length
+            offset += header_size + idf_len + idf_len % 2;
+        }
+
+        PathTable { dirs, names }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    pub fn get(&self, dir: usize) -> Option<&PathTableDir> {
+        self.dirs.get(dir)
+    }
+
+    fn children(&self, dir: usize) -> impl Iterator<Item = usize> + '_ {
+        // The root is its own parent
+        (0..self.dirs.len()).filter(move |&i| i != dir && self.dirs[i].parent == dir)
+    }
+
+    pub fn names_known(&self, dir: usize) -> bool {
+        self.children(dir).all(|i| self.dirs[i].name.is_some())
+    }
+
+    // Name the child directory stored at extent, from its record in the parent directory
+    pub fn set_name(&mut self, parent: usize, extent: u32, name: String) {
+        let child = self
+            .children(parent)
+            .find(|&i| self.dirs[i].extent == extent);
+        if let Some(child) = child {
+            self.dirs[child].name = Some(name);
+        }
+    }
+
+    pub fn find_child(&self, dir: usize, path_component: &str) -> Option<usize> {
+        let upper = path_component.to_uppercase();
+        self.children(dir).find(|&i| match &self.dirs[i].name {
+            Some(name) if self.names == PathTableNames::Iso9660 => *name == upper,
+            Some(name) => name == path_component,
+            None => false,
+        })
+    }
+}
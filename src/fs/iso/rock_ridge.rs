@@ -0,0 +1,197 @@
+// Rock Ridge Interchange Protocol, stored as System Use Sharing Protocol entries
+// in the System Use area of directory records
+
+use super::iso9660::{iso_date_to_unix, iso_long_date_to_unix, ISO_DATE_LEN, ISO_LDATE_LEN};
+use super::zisofs::{Zisofs, ZISOFS_ALGORITHM};
+
+use alloc::string::String;
+use core::convert::TryInto;
+
+const SUSP_HEADER_LEN: usize = 4; // Signature, length and version
+
+// NM flags
+const NM_CURRENT: u8 = 0x2;
+const NM_PARENT: u8 = 0x4;
+
+// SL component flags
+const SL_CONTINUE: u8 = 0x1;
+const SL_CURRENT: u8 = 0x2;
+const SL_PARENT: u8 = 0x4;
+const SL_ROOT: u8 = 0x8;
+
+// TF flags, timestamps are recorded in the order of these bits
+const TF_CREATION: u8 = 0x1;
+const TF_MODIFY: u8 = 0x2;
+const TF_ACCESS: u8 = 0x4;
+const TF_ATTRIBUTES: u8 = 0x8;
+const TF_LONG_FORM: u8 = 0x80;
+
+// Continuation area holding more System Use entries
+#[derive(Debug, Clone, Copy)]
+pub struct Continuation {
+    pub block: u32,
+    pub offset: u32,
+    pub len: u32,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RockRidge {
+    pub name: Option<String>,    // NM, alternate name
+    pub mode: Option<u32>,       // PX, POSIX file mode
+    pub nlink: Option<u32>,      // PX, number of links
+    pub uid: Option<u32>,        // PX, owner user id
+    pub gid: Option<u32>,        // PX, owner group id
+    pub mtime: Option<i64>,      // TF, modification time
+    pub atime: Option<i64>,      // TF, access time
+    pub ctime: Option<i64>,      // TF, attributes change time
+    pub symlink: Option<String>, // SL, symbolic link target
+    pub zisofs: Option<Zisofs>,  // ZF, compressed file data
+
+    symlink_continue: bool, // Last SL component continues in the next one
+}
+
+fn read_le32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+impl RockRidge {
+    pub fn is_present(&self) -> bool {
+        self.name.is_some() || self.mode.is_some() || self.mtime.is_some() || self.symlink.is_some()
+    }
+
+    // Parse the entries of a System Use or continuation area, returning
+    // the continuation area to parse next if any
+    pub fn parse(&mut self, area: &[u8]) -> Option<Continuation> {
+        let mut continuation: Option<Continuation> = None;
+        let mut offset: usize = 0;
+
+        while offset + SUSP_HEADER_LEN <= area.len() {
+            let len = area[offset + 2] as usize;
+            if len < SUSP_HEADER_LEN || offset + len > area.len() {
+                break;
+            }
+            let data = &area[offset + SUSP_HEADER_LEN..offset + len];
+
+            match &area[offset..offset + 2] {
+                b"NM" => self.parse_nm(data),
+                b"PX" => self.parse_px(data),
+                b"TF" => self.parse_tf(data),
+                b"SL" => self.parse_sl(data),
+                b"ZF" => self.zisofs = parse_zf(data),
+                b"CE" => continuation = parse_ce(data),
+                b"ST" => break,
+                _ => {}
+            }
+
+            offset += len;
+        }
+
+        continuation
+    }
+
+    fn parse_nm(&mut self, data: &[u8]) {
+        let (flags, content) = match data.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        let name = self.name.get_or_insert_with(String::new);
+        if flags & NM_CURRENT != 0 {
+            name.push('.');
+        } else if flags & NM_PARENT != 0 {
+            name.push_str("..");
+        } else {
+            name.push_str(&String::from_utf8_lossy(content));
+        }
+    }
+
+    fn parse_px(&mut self, data: &[u8]) {
+        // Fields are both-endian, only the little endian half is read
+        self.mode = read_le32(data, 0);
+        self.nlink = read_le32(data, 8);
+        self.uid = read_le32(data, 16);
+        self.gid = read_le32(data, 24);
+    }
+
+    fn parse_tf(&mut self, data: &[u8]) {
+        let (flags, mut stamps) = match data.split_first() {
+            Some((flags, stamps)) => (*flags, stamps),
+            None => return,
+        };
+        let stamp_len = if flags & TF_LONG_FORM != 0 {
+            ISO_LDATE_LEN
+        } else {
+            ISO_DATE_LEN
+        };
+
+        for bit in [TF_CREATION, TF_MODIFY, TF_ACCESS, TF_ATTRIBUTES] {
+            if flags & bit == 0 {
+                continue;
+            }
+            if stamps.len() < stamp_len {
+                return;
+            }
+            let time = match stamp_len {
+                ISO_LDATE_LEN => iso_long_date_to_unix(stamps[..stamp_len].try_into().unwrap()),
+                _ => iso_date_to_unix(stamps[..stamp_len].try_into().unwrap()),
+            };
+            match bit {
+                TF_MODIFY => self.mtime = Some(time),
+                TF_ACCESS => self.atime = Some(time),
+                TF_ATTRIBUTES => self.ctime = Some(time),
+                _ => {}
+            }
+            stamps = &stamps[stamp_len..];
+        }
+    }
+
+    fn parse_sl(&mut self, data: &[u8]) {
+        // First byte holds the SL flags, followed by component records
+        let mut components = match data.get(1..) {
+            Some(components) => components,
+            None => return,
+        };
+        let link = self.symlink.get_or_insert_with(String::new);
+
+        while components.len() >= 2 {
+            let flags = components[0];
+            let len = components[1] as usize;
+            let content = match components.get(2..2 + len) {
+                Some(content) => content,
+                None => return,
+            };
+
+            if !self.symlink_continue && !link.is_empty() && !link.ends_with('/') {
+                link.push('/');
+            }
+            if flags & SL_ROOT != 0 {
+                link.push('/');
+            } else if flags & SL_CURRENT != 0 {
+                link.push('.');
+            } else if flags & SL_PARENT != 0 {
+                link.push_str("..");
+            } else {
+                link.push_str(&String::from_utf8_lossy(content));
+            }
+            self.symlink_continue = flags & SL_CONTINUE != 0;
+
+            components = &components[2 + len..];
+        }
+    }
+}
+
+fn parse_ce(data: &[u8]) -> Option<Continuation> {
+    Some(Continuation {
+        block: read_le32(data, 0)?,
+        offset: read_le32(data, 8)?,
+        len: read_le32(data, 16)?,
+    })
+}
+
+// Algorithm, header size / 4, log2 of the block size and uncompressed size
+fn parse_zf(data: &[u8]) -> Option<Zisofs> {
+    if data.get(..2)? != ZISOFS_ALGORITHM {
+        return None;
+    }
+    Zisofs::new(read_le32(data, 4)?, *data.get(2)?, *data.get(3)?)
+}
@@ -0,0 +1,148 @@
+// zisofs, files compressed block by block with zlib by mkisofs -z or xorriso.
+// A header is followed by a table of block pointers then the compressed blocks.
+
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::utils::inflate::zlib_decompress;
+
+use super::entry::IsoEntry;
+use super::fd::read_exact;
+
+use alloc::{vec, vec::Vec};
+use core::cmp::min;
+use core::convert::TryInto;
+
+pub const ZISOFS_MAGIC: [u8; 8] = [0x37, 0xe4, 0x53, 0x96, 0xc9, 0xdb, 0xd6, 0x07];
+pub const ZISOFS_ALGORITHM: &[u8; 2] = b"pz"; // In Rock Ridge ZF entries
+const ZISOFS_HEADER_LEN: usize = 16;
+const ZISOFS_POINTER_LEN: u64 = 4;
+
+// Block sizes of 32, 64 or 128 KiB
+const MIN_BLOCK_SHIFT: u8 = 15;
+const MAX_BLOCK_SHIFT: u8 = 17;
+
+// Compression parameters, from the Rock Ridge ZF entry or the file header
+#[derive(Debug, Clone, Copy)]
+pub struct Zisofs {
+    pub size: u64,        // Uncompressed size
+    pub header_size: u64, // Offset of the block pointers
+    pub block_shift: u8,  // Log2 of the block size
+}
+
+impl Zisofs {
+    pub fn new(size: u32, header_size_div4: u8, block_shift: u8) -> Option<Self> {
+        let header_size = header_size_div4 as u64 * 4;
+        if header_size < ZISOFS_HEADER_LEN as u64
+            || !(MIN_BLOCK_SHIFT..=MAX_BLOCK_SHIFT).contains(&block_shift)
+        {
+            return None;
+        }
+        Some(Zisofs {
+            size: size as u64,
+            header_size,
+            block_shift,
+        })
+    }
+
+    // Magic, uncompressed size, header size / 4 and log2 of the block size
+    fn parse_header(header: &[u8; ZISOFS_HEADER_LEN]) -> Option<Self> {
+        if header[..8] != ZISOFS_MAGIC {
+            return None;
+        }
+        let size = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        Zisofs::new(size, header[12], header[13])
+    }
+
+    // Files without a ZF entry are recognized by the header magic
+    pub async fn detect(entry: &IsoEntry) -> SysResult<Option<Self>> {
+        if let Some(zisofs) = entry.rock_ridge.zisofs {
+            return Ok(Some(zisofs));
+        }
+        if entry.size() < ZISOFS_HEADER_LEN as u64 {
+            return Ok(None);
+        }
+        let mut header = [0u8; ZISOFS_HEADER_LEN];
+        read_exact(entry, &mut header, 0).await?;
+        Ok(Zisofs::parse_header(&header))
+    }
+
+    pub fn block_size(&self) -> u64 {
+        1 << self.block_shift
+    }
+}
+
+// Decompressed blocks of a zisofs file, the last one read being kept
+pub struct ZisofsReader {
+    pub zisofs: Zisofs,
+    cache: Option<(u64, Vec<u8>)>, // Block index and data
+}
+
+impl ZisofsReader {
+    pub fn new(zisofs: Zisofs) -> Self {
+        ZisofsReader {
+            zisofs,
+            cache: None,
+        }
+    }
+
+    // Only the blocks holding the requested range are decompressed
+    pub async fn pread(
+        &mut self,
+        entry: &IsoEntry,
+        buf: &mut [u8],
+        count: usize,
+        offset: u64,
+    ) -> SysResult<usize> {
+        let count = min(count, buf.len());
+        let mut offset = offset;
+        let mut read: usize = 0;
+
+        while read < count && offset < self.zisofs.size {
+            let index = offset >> self.zisofs.block_shift;
+            let block_offset = (offset - (index << self.zisofs.block_shift)) as usize;
+            let data = self.block(entry, index).await?;
+            let len = min(count - read, data.len() - block_offset);
+            buf[read..read + len].copy_from_slice(&data[block_offset..block_offset + len]);
+            read += len;
+            offset += len as u64;
+        }
+
+        Ok(read)
+    }
+
+    // Block pointers are offsets in the file of the compressed blocks, the
+    // next pointer ending each one. Blocks of zeros have no compressed data.
+    async fn block(&mut self, entry: &IsoEntry, index: u64) -> SysResult<&[u8]> {
+        let cached = matches!(self.cache, Some((cached, _)) if cached == index);
+        if !cached {
+            let zisofs = self.zisofs;
+            let start = index << zisofs.block_shift;
+            let len = min(zisofs.block_size(), zisofs.size - start) as usize;
+
+            let mut pointers = [0u8; 2 * ZISOFS_POINTER_LEN as usize];
+            let offset = zisofs.header_size + index * ZISOFS_POINTER_LEN;
+            read_exact(entry, &mut pointers, offset).await?;
+            let from = u32::from_le_bytes(pointers[..4].try_into().unwrap()) as u64;
+            let to = u32::from_le_bytes(pointers[4..].try_into().unwrap()) as u64;
+            // zlib never grows a block by more than a few bytes
+            if to < from || to - from > 2 * zisofs.block_size() {
+                return Err(Errno::EIO);
+            }
+
+            // The previous block is freed before allocating the next one
+            self.cache = None;
+            let data = match to - from {
+                0 => vec![0u8; len],
+                compressed_len => {
+                    let mut compressed = vec![0u8; compressed_len as usize];
+                    read_exact(entry, &mut compressed, from).await?;
+                    zlib_decompress(&compressed, len).ok_or(Errno::EIO)?
+                }
+            };
+            if data.len() != len {
+                return Err(Errno::EIO);
+            }
+            self.cache = Some((index, data));
+        }
+        Ok(&self.cache.as_ref().unwrap().1)
+    }
+}
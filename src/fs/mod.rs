@@ -1,82 +1,412 @@
+pub mod devfs;
+pub mod initramfs;
 pub mod iso;
+pub mod overlay;
+pub mod path;
+pub mod procfs;
+pub mod tmpfs;
 
-use crate::fd::FDt;
+use crate::drivers::block::{loop_device, DeviceId, BLOCK_DEVICES};
+use crate::fd::{FDId, FDt, FD_TABLE};
+use crate::println;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_RDONLY, S_IFDIR};
 use crate::utils::mutex::AsyncMutex;
 
-use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use async_trait::async_trait;
-use core::cell::RefCell;
 use lazy_static::lazy_static;
+use path::PathError;
 use prefix_tree_map::{PrefixTreeMap, PrefixTreeMapBuilder};
 
-pub type FSt = Arc<RefCell<dyn FileSystem>>;
+pub type FSt = Arc<AsyncMutex<dyn FileSystem>>;
 
 lazy_static! {
     pub static ref VIRTUAL_FS: AsyncMutex<VirtualFS> = AsyncMutex::new(VirtualFS::new());
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+    CharDevice,
+    BlockDevice,
+    Fifo,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub file_type: FileType,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Stat {
+    pub file_type: FileType,
+    pub size: u64,
+    pub block: u64, // Location of the first data block
+    pub mtime: i64, // Modification time, in seconds since the Unix epoch
+    pub mode: u32,  // File type and permission bits
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
 #[async_trait(?Send)]
 pub trait FileSystem {
-    async fn open(&mut self, path: &str, flags: u32) -> Option<FDt>;
+    async fn open(&mut self, path: &str, flags: u32) -> SysResult<FDt>;
+    async fn readdir(&mut self, path: &str) -> SysResult<Vec<DirEntry>>;
+    async fn stat(&mut self, path: &str) -> SysResult<Stat>;
+    async fn readlink(&mut self, path: &str) -> SysResult<String>;
+    async fn mkdir(&mut self, path: &str) -> SysResult<()>;
+    async fn unlink(&mut self, path: &str) -> SysResult<()>;
+    async fn rmdir(&mut self, path: &str) -> SysResult<()>;
+}
+
+// File system driver, creating file systems from their source device
+#[async_trait(?Send)]
+pub trait FileSystemType {
+    fn name(&self) -> &'static str;
+    async fn mount(&self, source: Option<DeviceId>) -> SysResult<FSt>;
+}
+
+pub type FsTypet = Arc<dyn FileSystemType>;
+
+#[derive(Clone)]
+pub struct Mount {
+    pub fs_type: &'static str,
+    pub source: Option<String>, // Block device name
+    pub target: String,
+    components: Vec<String>,
+    fs: FSt,
 }
 
 pub struct VirtualFS {
-    map_builder: PrefixTreeMapBuilder<String, String, FSt>,
     map: Option<PrefixTreeMap<String, String, FSt>>,
+    mounts: Vec<Mount>,
+    fs_types: BTreeMap<&'static str, FsTypet>,
+    open_fds: BTreeMap<FDId, String>, // Mount target of the descriptors opened through the VFS
 }
 
 impl VirtualFS {
     fn new() -> Self {
         let mut res = VirtualFS {
-            map_builder: PrefixTreeMapBuilder::new(),
             map: None,
+            mounts: Vec::new(),
+            fs_types: BTreeMap::new(),
+            open_fds: BTreeMap::new(),
         };
-        let fs = Arc::new(RefCell::new(iso::IsoFS {}));
-        let fs2 = Arc::new(RefCell::new(iso::IsoFS {}));
-        res.mount("/", fs);
-        res.mount("/mnt/iso", fs2);
+        res.register_fs_type(Arc::new(iso::IsoFSType {}));
+        res.register_fs_type(Arc::new(iso::BootFSType {}));
+        res.register_fs_type(Arc::new(tmpfs::TmpFSType {}));
+        res.register_fs_type(Arc::new(devfs::DevFSType {}));
+        res.register_fs_type(Arc::new(procfs::ProcFSType {}));
+        res.register_fs_type(Arc::new(initramfs::InitFSType {}));
         res
     }
 
-    fn mount(&mut self, path: &str, fs: FSt) {
-        let path_s: String = String::from(path);
-        self.map_builder.insert_exact(
-            path_s
-                .split("/")
-                .filter(|p| p != &"")
-                .map(|s| String::from(s)),
+    pub fn register_fs_type(&mut self, fs_type: FsTypet) {
+        self.fs_types.insert(fs_type.name(), fs_type);
+    }
+
+    pub fn fs_type(&self, name: &str) -> Option<FsTypet> {
+        self.fs_types.get(name).cloned()
+    }
+
+    // Mounts in mount order
+    pub fn mount_table(&self) -> &[Mount] {
+        &self.mounts
+    }
+
+    pub fn add_mount(
+        &mut self,
+        fs_type: &'static str,
+        source: Option<&str>,
+        target: &str,
+        fs: FSt,
+    ) -> SysResult<()> {
+        let components = path::normalize(target)?;
+        if self.mounts.iter().any(|m| m.components == components) {
+            return Err(Errno::EBUSY);
+        }
+
+        self.mounts.push(Mount {
+            fs_type,
+            source: source.map(String::from),
+            target: path::join(&components),
+            components,
             fs,
-        );
-        self.map = Some(self.map_builder.clone().build());
+        });
+        self.rebuild_map();
+        Ok(())
+    }
+
+    pub async fn remove_mount(&mut self, target: &str) -> SysResult<Mount> {
+        let components = path::normalize(target)?;
+        let index = self
+            .mounts
+            .iter()
+            .position(|m| m.components == components)
+            .ok_or(Errno::EINVAL)?;
+
+        // Mounts stacked under the target keep it in use
+        if self
+            .mounts
+            .iter()
+            .any(|m| m.components.len() > components.len() && m.components.starts_with(&components))
+        {
+            return Err(Errno::EBUSY);
+        }
+
+        // Closed descriptors are only removed from the FD table. Opening files
+        // locks the FD table with the VFS locked, it is always locked second.
+        let target = &self.mounts[index].target;
+        let fd_table = FD_TABLE.lock().await;
+        self.open_fds.retain(|fd, _| fd_table.contains(fd));
+        if self.open_fds.values().any(|t| t == target) {
+            return Err(Errno::EBUSY);
+        }
+
+        let mount = self.mounts.remove(index);
+        self.rebuild_map();
+        Ok(mount)
+    }
+
+    // The prefix tree map cannot remove keys, it is built again from the mount table
+    fn rebuild_map(&mut self) {
+        if self.mounts.is_empty() {
+            self.map = None;
+            return;
+        }
+
+        let mut map_builder: PrefixTreeMapBuilder<String, String, FSt> =
+            PrefixTreeMapBuilder::new();
+        for mount in self.mounts.iter() {
+            map_builder.insert_exact(mount.components.clone(), mount.fs.clone());
+        }
+        self.map = Some(map_builder.build());
+    }
+
+    // Mount points located directly under the directory
+    fn mount_points_under(&self, dir: &[String]) -> Vec<DirEntry> {
+        self.mounts
+            .iter()
+            .map(|m| &m.components)
+            .filter(|m| m.len() == dir.len() + 1 && m.starts_with(dir))
+            .map(|m| DirEntry {
+                name: m[dir.len()].clone(),
+                file_type: FileType::Directory,
+                size: 0,
+            })
+            .collect()
+    }
+
+    // Find the file system of the longest mount point prefixing the path,
+    // and the path relative to this mount point
+    pub fn resolve(&self, path: &str) -> Result<(FSt, String), PathError> {
+        let map = self.map.as_ref().ok_or(PathError::NoMountPoint)?;
+        let components = path::normalize(path)?;
+
+        for depth in (0..=components.len()).rev() {
+            if let Some(fs) = map.find_exact(&components[..depth]) {
+                return Ok((fs.clone(), path::join(&components[depth..])));
+            }
+        }
+        Err(PathError::NoMountPoint)
     }
 }
 
 #[async_trait(?Send)]
 impl FileSystem for VirtualFS {
-    async fn open(&mut self, path: &str, flags: u32) -> Option<FDt> {
-        if let Some(map) = &self.map {
-            let mut mnt_relative_path: String = String::from("");
-            let path_s: String = String::from(path);
-            let mut path_split: Vec<String> = path_s
-                .split("/")
-                .filter(|p| p != &"")
-                .map(|s| String::from(s))
-                .collect();
-            loop {
-                if let Some(fs) = map.find_exact(&path_split) {
-                    // TODO, remove path prefix of the mount point
-                    return fs
-                        .borrow_mut()
-                        .open(mnt_relative_path.as_str(), flags)
-                        .await;
-                } else {
-                    let component = path_split.remove(path_split.len() - 1);
-                    mnt_relative_path =
-                        String::from("/") + component.as_str() + mnt_relative_path.as_str();
-                }
+    async fn open(&mut self, path: &str, flags: u32) -> SysResult<FDt> {
+        let (fs, mnt_relative_path) = self.resolve(path)?;
+        let fd = fs
+            .lock()
+            .await
+            .open(mnt_relative_path.as_str(), flags)
+            .await?;
+
+        // Remember the mount of the descriptor, to refuse unmounting it while open
+        if let Some(mount) = self.mounts.iter().find(|m| Arc::ptr_eq(&m.fs, &fs)) {
+            self.open_fds
+                .insert(fd.lock().await.get_fd(), mount.target.clone());
+        }
+        Ok(fd)
+    }
+
+    async fn readdir(&mut self, path: &str) -> SysResult<Vec<DirEntry>> {
+        let (fs, mnt_relative_path) = self.resolve(path)?;
+        let entries = fs.lock().await.readdir(mnt_relative_path.as_str()).await;
+
+        // Mount points are listed even if the underlying directory does not exist
+        let mount_points = self.mount_points_under(&path::normalize(path)?);
+        let mut entries = match entries {
+            Err(Errno::ENOENT) if !mount_points.is_empty() => Vec::new(),
+            entries => entries?,
+        };
+
+        for mount_point in mount_points {
+            if !entries.iter().any(|e| e.name == mount_point.name) {
+                entries.push(mount_point);
             }
-        } else {
-            None
         }
+        Ok(entries)
+    }
+
+    async fn stat(&mut self, path: &str) -> SysResult<Stat> {
+        let (fs, mnt_relative_path) = self.resolve(path)?;
+        let res = fs.lock().await.stat(mnt_relative_path.as_str()).await;
+        match res {
+            Err(Errno::ENOENT) => {}
+            res => return res,
+        }
+
+        // Directories only holding mount points do not exist on the underlying file system
+        if self.mount_points_under(&path::normalize(path)?).is_empty() {
+            return Err(Errno::ENOENT);
+        }
+        Ok(Stat {
+            file_type: FileType::Directory,
+            size: 0,
+            block: 0,
+            mtime: 0,
+            mode: S_IFDIR | 0o555,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+        })
+    }
+
+    async fn readlink(&mut self, path: &str) -> SysResult<String> {
+        let (fs, mnt_relative_path) = self.resolve(path)?;
+        let mut fs = fs.lock().await;
+        fs.readlink(mnt_relative_path.as_str()).await
+    }
+
+    async fn mkdir(&mut self, path: &str) -> SysResult<()> {
+        let (fs, mnt_relative_path) = self.resolve(path)?;
+        let mut fs = fs.lock().await;
+        fs.mkdir(mnt_relative_path.as_str()).await
+    }
+
+    async fn unlink(&mut self, path: &str) -> SysResult<()> {
+        let (fs, mnt_relative_path) = self.resolve(path)?;
+        let mut fs = fs.lock().await;
+        fs.unlink(mnt_relative_path.as_str()).await
+    }
+
+    async fn rmdir(&mut self, path: &str) -> SysResult<()> {
+        let (fs, mnt_relative_path) = self.resolve(path)?;
+        let mut fs = fs.lock().await;
+        fs.rmdir(mnt_relative_path.as_str()).await
+    }
+}
+
+// File system of a registered type, read from the named block device
+async fn open_fs(fs_type: &str, source: Option<&str>) -> SysResult<(&'static str, FSt)> {
+    // Reading the file system may block, the VFS is not locked meanwhile
+    let fs_type = VIRTUAL_FS
+        .lock()
+        .await
+        .fs_type(fs_type)
+        .ok_or(Errno::ENODEV)?;
+    let device = match source {
+        Some(name) => Some(
+            BLOCK_DEVICES
+                .lock()
+                .await
+                .lookup(name)
+                .ok_or(Errno::ENXIO)?,
+        ),
+        None => None,
+    };
+    let fs = fs_type.mount(device).await?;
+    Ok((fs_type.name(), fs))
+}
+
+/// Mounts a file system of a registered type, read from the named block device,
+/// on the target path.
+pub async fn mount(fs_type: &str, source: Option<&str>, target: &str) -> SysResult<()> {
+    let (fs_type, fs) = open_fs(fs_type, source).await?;
+    VIRTUAL_FS
+        .lock()
+        .await
+        .add_mount(fs_type, source, target, fs)
+}
+
+/// Mounts an overlay of the file systems, the first ones on top, under a
+/// writable layer kept in memory, on the target path.
+pub async fn mount_overlay(lowers: Vec<FSt>, target: &str) -> SysResult<()> {
+    let fs = Arc::new(AsyncMutex::new(overlay::OverlayFS::new(lowers)));
+    VIRTUAL_FS
+        .lock()
+        .await
+        .add_mount("overlay", None, target, fs)
+}
+
+/// Unmounts the file system mounted on the target path, unless it is still in use.
+/// The loop device it was read from is detached, unless mounted elsewhere.
+pub async fn umount(target: &str) -> SysResult<()> {
+    let mount = VIRTUAL_FS.lock().await.remove_mount(target).await?;
+    let device = match mount.source {
+        Some(name) => BLOCK_DEVICES.lock().await.lookup(&name),
+        None => None,
+    };
+    if let Some(id) = device {
+        let _ = loop_device::detach(id).await;
+    }
+    Ok(())
+}
+
+async fn mount_or_log(fs_type: &str, source: Option<&str>, target: &str) {
+    if let Err(err) = mount(fs_type, source, target).await {
+        println!("Could not mount {} on {}: {}", fs_type, target, err);
+    }
+}
+
+// Mount the default file system hierarchy
+pub async fn init() {
+    // Kept in memory, they do not depend on any device
+    mount_or_log("devfs", None, "/dev").await;
+    mount_or_log("procfs", None, "/proc").await;
+    mount_or_log("tmpfs", None, "/tmp").await;
+
+    // The root is writable, over the initramfs when the boot loader provides one,
+    // and the ISO
+    let mut lowers = Vec::new();
+    for (fs_type, source) in [("initramfs", None), ("iso9660", Some("cdrom"))].iter() {
+        match open_fs(fs_type, *source).await {
+            Ok((_, fs)) => lowers.push(fs),
+            Err(err) => println!("Could not read {} for the root: {}", fs_type, err),
+        }
+    }
+    if let Err(err) = mount_overlay(lowers, "/").await {
+        println!("Could not mount overlay on /: {}", err);
+    }
+    mount_or_log("iso9660", Some("cdrom"), "/mnt/iso").await;
+    mount_or_log("eltorito", Some("cdrom"), "/mnt/boot").await;
+
+    if let Err(err) = mount_image("/images/test.iso", "/mnt/nested").await {
+        println!("Could not mount /images/test.iso on /mnt/nested: {}", err);
+    }
+}
+
+/// Mounts the ISO image stored in a file on the target path, through a loop device.
+pub async fn mount_image(path: &str, target: &str) -> SysResult<()> {
+    let fd = VIRTUAL_FS.lock().await.open(path, O_RDONLY).await?;
+    let attached = loop_device::attach(fd.clone()).await;
+    let (name, id) = match attached {
+        Ok(attached) => attached,
+        Err(err) => {
+            fd.lock().await.close().await;
+            return Err(err);
+        }
+    };
+    let res = mount("iso9660", Some(&name), target).await;
+    if res.is_err() {
+        let _ = loop_device::detach(id).await;
     }
+    res
 }
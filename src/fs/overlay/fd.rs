@@ -0,0 +1,83 @@
+use crate::fd::{FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{DirEntry, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::SEEK_SET;
+use crate::utils::AsyncMutex;
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use async_trait::async_trait;
+
+// Directory merged from the layers, listed when opened
+pub struct OverlayDirFD {
+    pub fd: FDId,
+    offset: usize, // Entry index
+    entries: Vec<DirEntry>,
+    stat: Stat,
+}
+
+impl OverlayDirFD {
+    pub async fn new(entries: Vec<DirEntry>, stat: Stat) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(OverlayDirFD {
+            fd: id,
+            offset: 0,
+            entries,
+            stat,
+        }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for OverlayDirFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, _buf: &[u8], _count: usize) -> SysResult<usize> {
+        Err(Errno::EBADF)
+    }
+
+    async fn read(&mut self, _buf: &mut [u8], _count: usize) -> SysResult<usize> {
+        Err(Errno::EISDIR)
+    }
+
+    async fn pread(&mut self, _buf: &mut [u8], _count: usize, _offset: u64) -> SysResult<usize> {
+        Err(Errno::EISDIR)
+    }
+
+    async fn pwrite(&mut self, _buf: &[u8], _count: usize, _offset: u64) -> SysResult<usize> {
+        Err(Errno::EBADF)
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    // Only rewinding is supported
+    async fn lseek(&mut self, offset: i64, whence: u32) -> SysResult<u64> {
+        if offset != 0 || whence != SEEK_SET {
+            return Err(Errno::EINVAL);
+        }
+        self.offset = 0;
+        Ok(0)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        let entry = self.entries.get(self.offset).cloned();
+        if entry.is_some() {
+            self.offset += 1;
+        }
+        Ok(entry)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(self.stat)
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EISDIR)
+    }
+}
@@ -0,0 +1,330 @@
+mod fd;
+
+use crate::fd::FDt;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY};
+use crate::utils::AsyncMutex;
+
+use super::tmpfs::{TmpFS, TMPFS_MAX_FILE_SIZE};
+use super::{path, DirEntry, FSt, FileSystem, FileType, Stat};
+use fd::OverlayDirFD;
+
+use alloc::{boxed::Box, collections::BTreeSet, string::String, sync::Arc, vec, vec::Vec};
+use async_trait::async_trait;
+
+const COPY_CHUNK_SIZE: usize = 4096;
+
+// Layer a path was found in
+struct Found {
+    fs: FSt,
+    stat: Stat,
+    upper: bool,
+}
+
+// Writable in-memory layer stacked over read-only file systems. Lower files
+// are copied up when first opened for writing, and deleting them records a
+// whiteout hiding them, along with everything under them.
+pub struct OverlayFS {
+    upper: FSt,
+    lowers: Vec<FSt>,                 // First ones on top
+    whiteouts: BTreeSet<Vec<String>>, // Deleted lower paths
+}
+
+impl OverlayFS {
+    pub fn new(lowers: Vec<FSt>) -> Self {
+        OverlayFS {
+            upper: Arc::new(AsyncMutex::new(TmpFS::new())),
+            lowers,
+            whiteouts: BTreeSet::new(),
+        }
+    }
+
+    // Whiteouts on the path or its parents hide it in the lower layers.
+    // A directory created again over a whiteout hides the lower contents.
+    fn lower_visible(&self, components: &[String]) -> bool {
+        (1..=components.len()).all(|depth| !self.whiteouts.contains(&components[..depth]))
+    }
+
+    // Upper layer first, then the lower ones in order
+    async fn lookup(&self, components: &[String]) -> SysResult<Found> {
+        let path = path::join(components);
+        let stat = self.upper.lock().await.stat(&path).await;
+        if let Ok(stat) = stat {
+            return Ok(Found {
+                fs: self.upper.clone(),
+                stat,
+                upper: true,
+            });
+        }
+        if self.lower_visible(components) {
+            for lower in self.lowers.iter() {
+                let stat = lower.lock().await.stat(&path).await;
+                if let Ok(stat) = stat {
+                    return Ok(Found {
+                        fs: lower.clone(),
+                        stat,
+                        upper: false,
+                    });
+                }
+            }
+        }
+        Err(Errno::ENOENT)
+    }
+
+    async fn in_lower(&self, components: &[String]) -> bool {
+        if !self.lower_visible(components) {
+            return false;
+        }
+        let path = path::join(components);
+        for lower in self.lowers.iter() {
+            let stat = lower.lock().await.stat(&path).await;
+            if stat.is_ok() {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Entries of every layer, the upper ones hiding the lower ones of the same name
+    async fn merged_entries(&self, components: &[String]) -> SysResult<Vec<DirEntry>> {
+        if self.lookup(components).await?.stat.file_type != FileType::Directory {
+            return Err(Errno::ENOTDIR);
+        }
+        let path = path::join(components);
+        let mut layers = vec![self.upper.clone()];
+        if self.lower_visible(components) {
+            layers.extend(self.lowers.iter().cloned());
+        }
+
+        let mut entries: Vec<DirEntry> = Vec::new();
+        for (depth, layer) in layers.iter().enumerate() {
+            // The path may be a file in this layer
+            let listing = match layer.lock().await.readdir(&path).await {
+                Ok(listing) => listing,
+                Err(_) => continue,
+            };
+            for entry in listing {
+                if entries.iter().any(|e| e.name == entry.name) {
+                    continue;
+                }
+                let mut child = components.to_vec();
+                child.push(entry.name.clone());
+                if depth > 0 && self.whiteouts.contains(&child) {
+                    continue;
+                }
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    // The parent directory must exist in one of the layers
+    async fn check_parent(&self, components: &[String]) -> SysResult<()> {
+        if components.len() <= 1 {
+            return Ok(());
+        }
+        let parent = self.lookup(&components[..components.len() - 1]).await?;
+        match parent.stat.file_type {
+            FileType::Directory => Ok(()),
+            _ => Err(Errno::ENOTDIR),
+        }
+    }
+
+    // Create the parent directories of the path missing in the upper layer
+    async fn copy_up_dirs(&self, components: &[String]) -> SysResult<()> {
+        for depth in 1..components.len() {
+            let dir = path::join(&components[..depth]);
+            let stat = self.upper.lock().await.stat(&dir).await;
+            match stat {
+                Ok(stat) if stat.file_type == FileType::Directory => continue,
+                Ok(_) => return Err(Errno::ENOTDIR),
+                Err(_) => {}
+            }
+            self.upper.lock().await.mkdir(&dir).await?;
+        }
+        Ok(())
+    }
+
+    // Copy a lower regular file to the upper layer, without its data when
+    // it is opened to be truncated
+    async fn copy_up(&self, components: &[String], found: &Found, flags: u32) -> SysResult<()> {
+        if found.stat.file_type != FileType::File {
+            return Err(Errno::EPERM);
+        }
+        let truncate = flags & O_TRUNC != 0;
+        if !truncate && found.stat.size > TMPFS_MAX_FILE_SIZE as u64 {
+            return Err(Errno::EFBIG);
+        }
+        self.copy_up_dirs(components).await?;
+        let path = path::join(components);
+
+        let dst = self
+            .upper
+            .lock()
+            .await
+            .open(&path, O_WRONLY | O_CREAT | O_TRUNC)
+            .await?;
+        let res = match truncate {
+            true => Ok(()),
+            false => self.copy_data(&found.fs, &path, &dst).await,
+        };
+        dst.lock().await.close().await;
+
+        // A partial copy would hide the lower file
+        if res.is_err() {
+            let _ = self.upper.lock().await.unlink(&path).await;
+        }
+        res
+    }
+
+    // The file is streamed a chunk at a time, never held whole in memory
+    async fn copy_data(&self, fs: &FSt, path: &str, dst: &FDt) -> SysResult<()> {
+        let src = fs.lock().await.open(path, O_RDONLY).await?;
+        let mut chunk = [0u8; COPY_CHUNK_SIZE];
+        let res = loop {
+            let read = match src.lock().await.read(&mut chunk, COPY_CHUNK_SIZE).await {
+                Ok(0) => break Ok(()),
+                Ok(read) => read,
+                Err(err) => break Err(err),
+            };
+            if let Err(err) = write_all(dst, &chunk[..read]).await {
+                break Err(err);
+            }
+        };
+        src.lock().await.close().await;
+        res
+    }
+}
+
+async fn write_all(fd: &FDt, data: &[u8]) -> SysResult<()> {
+    let mut written: usize = 0;
+    while written < data.len() {
+        let count = data.len() - written;
+        match fd.lock().await.write(&data[written..], count).await? {
+            0 => return Err(Errno::EIO),
+            n => written += n,
+        }
+    }
+    Ok(())
+}
+
+#[async_trait(?Send)]
+impl FileSystem for OverlayFS {
+    async fn open(&mut self, path: &str, flags: u32) -> SysResult<FDt> {
+        let components = path::normalize(path)?;
+        let path = path::join(&components);
+        let writing = flags & O_ACCMODE != O_RDONLY;
+
+        let found = match self.lookup(&components).await {
+            Ok(found) => found,
+            Err(Errno::ENOENT) if flags & O_CREAT != 0 => {
+                self.check_parent(&components).await?;
+                self.copy_up_dirs(&components).await?;
+                let fd = self.upper.lock().await.open(&path, flags).await;
+                return fd;
+            }
+            Err(err) => return Err(err),
+        };
+
+        if found.stat.file_type == FileType::Directory {
+            if writing {
+                return Err(Errno::EISDIR);
+            }
+            let entries = self.merged_entries(&components).await?;
+            return Ok(OverlayDirFD::new(entries, found.stat).await);
+        }
+        if !writing {
+            let fd = found.fs.lock().await.open(&path, flags).await;
+            return fd;
+        }
+        if !found.upper {
+            self.copy_up(&components, &found, flags).await?;
+        }
+        let fd = self.upper.lock().await.open(&path, flags).await;
+        fd
+    }
+
+    async fn readdir(&mut self, path: &str) -> SysResult<Vec<DirEntry>> {
+        self.merged_entries(&path::normalize(path)?).await
+    }
+
+    async fn stat(&mut self, path: &str) -> SysResult<Stat> {
+        Ok(self.lookup(&path::normalize(path)?).await?.stat)
+    }
+
+    async fn readlink(&mut self, path: &str) -> SysResult<String> {
+        let components = path::normalize(path)?;
+        let found = self.lookup(&components).await?;
+        let target = found
+            .fs
+            .lock()
+            .await
+            .readlink(&path::join(&components))
+            .await;
+        target
+    }
+
+    async fn mkdir(&mut self, path: &str) -> SysResult<()> {
+        let components = path::normalize(path)?;
+        if self.lookup(&components).await.is_ok() {
+            return Err(Errno::EEXIST);
+        }
+        self.check_parent(&components).await?;
+        self.copy_up_dirs(&components).await?;
+        let res = self
+            .upper
+            .lock()
+            .await
+            .mkdir(&path::join(&components))
+            .await;
+        res
+    }
+
+    async fn unlink(&mut self, path: &str) -> SysResult<()> {
+        let components = path::normalize(path)?;
+        let found = self.lookup(&components).await?;
+        if found.stat.file_type == FileType::Directory {
+            return Err(Errno::EISDIR);
+        }
+        if found.upper {
+            self.upper
+                .lock()
+                .await
+                .unlink(&path::join(&components))
+                .await?;
+        }
+        if self.in_lower(&components).await {
+            self.whiteouts.insert(components);
+        }
+        Ok(())
+    }
+
+    async fn rmdir(&mut self, path: &str) -> SysResult<()> {
+        let components = path::normalize(path)?;
+        if components.is_empty() {
+            return Err(Errno::EBUSY);
+        }
+        let found = self.lookup(&components).await?;
+        if found.stat.file_type != FileType::Directory {
+            return Err(Errno::ENOTDIR);
+        }
+        let entries = self.merged_entries(&components).await?;
+        if entries.iter().any(|e| e.name != "." && e.name != "..") {
+            return Err(Errno::ENOTEMPTY);
+        }
+
+        if found.upper {
+            self.upper
+                .lock()
+                .await
+                .rmdir(&path::join(&components))
+                .await?;
+        }
+        if self.in_lower(&components).await {
+            // The whiteout hides everything under the directory
+            self.whiteouts.retain(|w| !w.starts_with(&components));
+            self.whiteouts.insert(components);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    NotAbsolute,  // Path does not start at the root
+    NoMountPoint, // No file system is mounted on any prefix of the path
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathError::NotAbsolute => write!(f, "path is not absolute"),
+            PathError::NoMountPoint => write!(f, "no file system mounted for path"),
+        }
+    }
+}
+
+/// Splits an absolute path into its components, dropping empty and `.`
+/// components and resolving `..` against the previous one.
+/// `..` at the root stays at the root.
+pub fn normalize(path: &str) -> Result<Vec<String>, PathError> {
+    if !path.starts_with("/") {
+        return Err(PathError::NotAbsolute);
+    }
+
+    let mut components: Vec<String> = Vec::new();
+    for component in path.split("/") {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            c => components.push(String::from(c)),
+        }
+    }
+    Ok(components)
+}
+
+/// Builds an absolute path back from normalized components.
+pub fn join(components: &[String]) -> String {
+    let mut path = String::new();
+    for component in components {
+        path.push('/');
+        path.push_str(component.as_str());
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+    path
+}
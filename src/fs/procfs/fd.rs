@@ -0,0 +1,97 @@
+use crate::fd::{seek_offset, FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{DirEntry, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{SEEK_END, SEEK_SET};
+use crate::utils::AsyncMutex;
+
+use super::ProcNode;
+
+use alloc::{boxed::Box, string::String, sync::Arc};
+use async_trait::async_trait;
+use core::convert::TryFrom;
+
+pub struct ProcFD {
+    pub fd: FDId,
+    offset: usize,
+    node: ProcNode,
+    content: Option<String>, // Generated on the first read, kept until a rewind
+}
+
+impl ProcFD {
+    pub async fn new(node: ProcNode) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(ProcFD {
+            fd: id,
+            offset: 0,
+            node,
+            content: None,
+        }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for ProcFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, _buf: &[u8], _count: usize) -> SysResult<usize> {
+        Err(Errno::EBADF)
+    }
+
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        let read = self.pread(buf, count, self.offset as u64).await?;
+        self.offset += read;
+        Ok(read)
+    }
+
+    // Reads at any offset come from the same snapshot of the contents
+    async fn pread(&mut self, buf: &mut [u8], count: usize, offset: u64) -> SysResult<usize> {
+        if self.content.is_none() {
+            self.content = Some(self.node.generate().await);
+        }
+        let content = self.content.as_ref().unwrap().as_bytes();
+
+        let offset = usize::try_from(offset).map_err(|_| Errno::EINVAL)?;
+        if offset >= content.len() {
+            return Ok(0);
+        }
+        let count = core::cmp::min(core::cmp::min(count, buf.len()), content.len() - offset);
+        buf[..count].copy_from_slice(&content[offset..offset + count]);
+
+        Ok(count)
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    // Rewinding generates the contents again
+    async fn lseek(&mut self, offset: i64, whence: u32) -> SysResult<u64> {
+        // The size is unknown until the contents are generated
+        if whence == SEEK_END {
+            return Err(Errno::EINVAL);
+        }
+        let new_offset = seek_offset(self.offset as u64, 0, offset, whence)?;
+        if whence == SEEK_SET && offset == 0 {
+            self.content = None;
+        }
+        self.offset = new_offset as usize;
+        Ok(new_offset)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        Err(Errno::ENOTDIR)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(self.node.stat())
+    }
+
+    async fn ftruncate(&mut self, _length: usize) -> SysResult<()> {
+        Err(Errno::EINVAL)
+    }
+}
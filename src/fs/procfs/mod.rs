@@ -0,0 +1,276 @@
+mod fd;
+
+use crate::drivers::block::cache::BLOCK_CACHE;
+use crate::drivers::block::DeviceId;
+use crate::fd::{FDt, FD_TABLE};
+use crate::interrupts::pic::pit;
+use crate::memory::heap_alloc::ALLOCATOR;
+use crate::memory::{FRAME_ALLOCATOR, PAGE_SIZE};
+use crate::proc::scheduler::SCHEDULER;
+use crate::proc::thread::STACK_SIZE;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_RDONLY, S_IFDIR, S_IFREG};
+use crate::utils::AsyncMutex;
+
+use super::{path, DirEntry, FSt, FileSystem, FileSystemType, FileType, Stat, VIRTUAL_FS};
+use fd::ProcFD;
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use async_trait::async_trait;
+use core::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProcNode {
+    Threads,
+    Fds,
+    Heap,
+    Frames,
+    Mounts,
+    Ticks,
+    BlockCache,
+}
+
+const PROC_NODES: [ProcNode; 7] = [
+    ProcNode::Threads,
+    ProcNode::Fds,
+    ProcNode::Heap,
+    ProcNode::Frames,
+    ProcNode::Mounts,
+    ProcNode::Ticks,
+    ProcNode::BlockCache,
+];
+
+impl ProcNode {
+    fn name(&self) -> &'static str {
+        match self {
+            ProcNode::Threads => "threads",
+            ProcNode::Fds => "fds",
+            ProcNode::Heap => "heap",
+            ProcNode::Frames => "frames",
+            ProcNode::Mounts => "mounts",
+            ProcNode::Ticks => "ticks",
+            ProcNode::BlockCache => "blockcache",
+        }
+    }
+
+    // Contents are generated when read, the size is unknown beforehand
+    fn stat(&self) -> Stat {
+        Stat {
+            file_type: FileType::File,
+            size: 0,
+            block: 0,
+            mtime: 0,
+            mode: S_IFREG | 0o444,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    fn dir_entry(&self) -> DirEntry {
+        DirEntry {
+            name: String::from(self.name()),
+            file_type: FileType::File,
+            size: 0,
+        }
+    }
+
+    // Writing to a String never fails
+    async fn generate(&self) -> String {
+        let mut out = String::new();
+        match self {
+            ProcNode::Threads => {
+                let scheduler = SCHEDULER.lock().await;
+                writeln!(out, "id\tstate\tstack").unwrap();
+                for (id, thread) in scheduler.threads.iter() {
+                    let thread = thread.borrow();
+                    let state = match (thread.started, thread.is_blocked) {
+                        (_, true) => "blocked",
+                        (true, false) => "started",
+                        (false, false) => "ready",
+                    };
+                    // The kernel thread runs on the boot stack
+                    match thread.base_stack {
+                        0 => writeln!(out, "{}\t{}\t-", id.0, state).unwrap(),
+                        base => writeln!(
+                            out,
+                            "{}\t{}\t{:#x}-{:#x}",
+                            id.0,
+                            state,
+                            base,
+                            base + STACK_SIZE as u64
+                        )
+                        .unwrap(),
+                    }
+                }
+            }
+            ProcNode::Fds => {
+                let fd_table = FD_TABLE.lock().await;
+                writeln!(out, "fd\ttype\tsize").unwrap();
+                for (id, fd) in fd_table.iter() {
+                    // The descriptor being read is already locked
+                    let stat = match fd.try_lock() {
+                        Some(fd) => fd.fstat().await.ok(),
+                        None => None,
+                    };
+                    match stat {
+                        Some(stat) => {
+                            writeln!(out, "{:?}\t{:?}\t{}", id, stat.file_type, stat.size).unwrap()
+                        }
+                        None => writeln!(out, "{:?}\t-\t-", id).unwrap(),
+                    }
+                }
+            }
+            ProcNode::Heap => {
+                // Formatting allocates, the heap lock is released first
+                let (bottom, size, used, free) = {
+                    let heap = ALLOCATOR.lock();
+                    (heap.bottom(), heap.size(), heap.used(), heap.free())
+                };
+                writeln!(out, "start\t{:#x}", bottom).unwrap();
+                writeln!(out, "size\t{}", size).unwrap();
+                writeln!(out, "used\t{}", used).unwrap();
+                writeln!(out, "free\t{}", free).unwrap();
+            }
+            ProcNode::Frames => match FRAME_ALLOCATOR.lock().as_ref() {
+                Some(allocator) => {
+                    writeln!(out, "frame size\t{}", PAGE_SIZE).unwrap();
+                    writeln!(out, "allocated\t{}", allocator.allocated_frames()).unwrap();
+                    writeln!(out, "free\t{}", allocator.free_frames()).unwrap();
+                    writeln!(
+                        out,
+                        "next free\t{:#x}",
+                        allocator.next_free_frame().start_address().as_u64()
+                    )
+                    .unwrap();
+                    if let Some((base, length)) = allocator.current_area() {
+                        writeln!(out, "area\t{:#x}-{:#x}", base, base + length).unwrap();
+                    }
+                }
+                None => writeln!(out, "uninitialized").unwrap(),
+            },
+            ProcNode::Mounts => {
+                let vfs = VIRTUAL_FS.lock().await;
+                writeln!(out, "source\ttarget\ttype").unwrap();
+                for mount in vfs.mount_table() {
+                    let source = mount.source.as_deref().unwrap_or("none");
+                    writeln!(out, "{}\t{}\t{}", source, mount.target, mount.fs_type).unwrap();
+                }
+            }
+            ProcNode::Ticks => writeln!(out, "{}", pit::gettick()).unwrap(),
+            ProcNode::BlockCache => {
+                let (hits, misses, len, capacity) = {
+                    let cache = BLOCK_CACHE.lock().await;
+                    (cache.hits(), cache.misses(), cache.len(), cache.capacity())
+                };
+                writeln!(out, "hits\t{}", hits).unwrap();
+                writeln!(out, "misses\t{}", misses).unwrap();
+                writeln!(out, "blocks\t{}", len).unwrap();
+                writeln!(out, "capacity\t{}", capacity).unwrap();
+            }
+        }
+        out
+    }
+}
+
+pub struct ProcFSType {}
+
+#[async_trait(?Send)]
+impl FileSystemType for ProcFSType {
+    fn name(&self) -> &'static str {
+        "procfs"
+    }
+
+    // Not backed by any device
+    async fn mount(&self, _source: Option<DeviceId>) -> SysResult<FSt> {
+        Ok(Arc::new(AsyncMutex::new(ProcFS::new())))
+    }
+}
+
+// Kernel state, exposed as read only files generated on read
+pub struct ProcFS {}
+
+impl ProcFS {
+    pub fn new() -> Self {
+        ProcFS {}
+    }
+
+    fn find(&self, name: &str) -> Option<ProcNode> {
+        PROC_NODES.iter().find(|node| node.name() == name).copied()
+    }
+
+    // None for the root directory
+    fn lookup(&self, path: &str) -> SysResult<Option<ProcNode>> {
+        let components = path::normalize(path)?;
+        match components.as_slice() {
+            [] => Ok(None),
+            [name] => self.find(name).map(Some).ok_or(Errno::ENOENT),
+            // Every node is a file under the root
+            [name, ..] => match self.find(name) {
+                Some(_) => Err(Errno::ENOTDIR),
+                None => Err(Errno::ENOENT),
+            },
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl FileSystem for ProcFS {
+    async fn open(&mut self, path: &str, flags: u32) -> SysResult<FDt> {
+        let node = self.lookup(path)?.ok_or(Errno::EISDIR)?;
+        if flags & O_ACCMODE != O_RDONLY {
+            return Err(Errno::EACCES);
+        }
+        Ok(ProcFD::new(node).await)
+    }
+
+    async fn readdir(&mut self, path: &str) -> SysResult<Vec<DirEntry>> {
+        if self.lookup(path)?.is_some() {
+            return Err(Errno::ENOTDIR);
+        }
+        let mut entries = Vec::new();
+        for name in [".", ".."] {
+            entries.push(DirEntry {
+                name: String::from(name),
+                file_type: FileType::Directory,
+                size: 0,
+            });
+        }
+        entries.extend(PROC_NODES.iter().map(|node| node.dir_entry()));
+        Ok(entries)
+    }
+
+    async fn stat(&mut self, path: &str) -> SysResult<Stat> {
+        match self.lookup(path)? {
+            Some(node) => Ok(node.stat()),
+            None => Ok(Stat {
+                file_type: FileType::Directory,
+                size: PROC_NODES.len() as u64,
+                block: 0,
+                mtime: 0,
+                mode: S_IFDIR | 0o555,
+                nlink: 2,
+                uid: 0,
+                gid: 0,
+            }),
+        }
+    }
+
+    async fn readlink(&mut self, path: &str) -> SysResult<String> {
+        self.lookup(path)?;
+        Err(Errno::EINVAL)
+    }
+
+    // The nodes are fixed
+    async fn mkdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EPERM)
+    }
+
+    async fn unlink(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EPERM)
+    }
+
+    async fn rmdir(&mut self, _path: &str) -> SysResult<()> {
+        Err(Errno::EPERM)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::fd::{seek_offset, FDId, FDt, FileDescriptor, FD_TABLE};
+use crate::fs::{DirEntry, Stat};
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_APPEND, O_RDONLY, O_WRONLY};
+use crate::utils::AsyncMutex;
+
+use super::{TmpNode, TmpNodet, TMPFS_MAX_FILE_SIZE};
+
+use alloc::{boxed::Box, sync::Arc};
+use async_trait::async_trait;
+
+pub struct TmpFD {
+    pub fd: FDId,
+    offset: usize, // Byte offset for files, entry index for directories
+    flags: u32,
+    node: TmpNodet,
+}
+
+impl TmpFD {
+    pub async fn new(node: TmpNodet, flags: u32) -> FDt {
+        let id = FDId::new();
+        let fd = Arc::new(AsyncMutex::new(TmpFD {
+            fd: id,
+            offset: 0,
+            flags,
+            node,
+        }));
+
+        FD_TABLE.lock().await.register_fd(id, fd.clone());
+        fd
+    }
+}
+
+#[async_trait(?Send)]
+impl FileDescriptor for TmpFD {
+    fn get_fd(&self) -> FDId {
+        self.fd
+    }
+
+    async fn write(&mut self, buf: &[u8], count: usize) -> SysResult<usize> {
+        if self.flags & O_ACCMODE == O_RDONLY {
+            return Err(Errno::EBADF);
+        }
+        let mut node = self.node.borrow_mut();
+        let file = match &mut *node {
+            TmpNode::File(file) => file,
+            TmpNode::Directory(_) => return Err(Errno::EISDIR),
+        };
+
+        if self.flags & O_APPEND != 0 {
+            self.offset = file.data().len();
+        }
+        if self.offset >= TMPFS_MAX_FILE_SIZE {
+            return Err(Errno::EFBIG);
+        }
+        let count = core::cmp::min(
+            core::cmp::min(count, buf.len()),
+            TMPFS_MAX_FILE_SIZE - self.offset,
+        );
+        let end = self.offset + count;
+        // Writing past the end leaves a zero filled gap
+        if end > file.data().len() {
+            file.resize(end)?;
+        }
+        file.data_mut()[self.offset..end].copy_from_slice(&buf[..count]);
+        self.offset = end;
+
+        Ok(count)
+    }
+
+    async fn read(&mut self, buf: &mut [u8], count: usize) -> SysResult<usize> {
+        if self.flags & O_ACCMODE == O_WRONLY {
+            return Err(Errno::EBADF);
+        }
+        let node = self.node.borrow();
+        let data = match &*node {
+            TmpNode::File(file) => file.data(),
+            TmpNode::Directory(_) => return Err(Errno::EISDIR),
+        };
+
+        if self.offset >= data.len() {
+            return Ok(0);
+        }
+        let count = core::cmp::min(core::cmp::min(count, buf.len()), data.len() - self.offset);
+        buf[..count].copy_from_slice(&data[self.offset..self.offset + count]);
+        self.offset += count;
+
+        Ok(count)
+    }
+
+    async fn close(&mut self) {
+        FD_TABLE.lock().await.unregister_fd(self);
+    }
+
+    async fn lseek(&mut self, offset: i64, whence: u32) -> SysResult<u64> {
+        let size = self.node.borrow().size();
+        self.offset = seek_offset(self.offset as u64, size, offset, whence)? as usize;
+        Ok(self.offset as u64)
+    }
+
+    async fn readdir(&mut self) -> SysResult<Option<DirEntry>> {
+        let entry = self.node.borrow().entry_at(self.offset)?;
+        if entry.is_some() {
+            self.offset += 1;
+        }
+        Ok(entry)
+    }
+
+    async fn fstat(&self) -> SysResult<Stat> {
+        Ok(self.node.borrow().stat())
+    }
+
+    async fn ftruncate(&mut self, length: usize) -> SysResult<()> {
+        if self.flags & O_ACCMODE == O_RDONLY {
+            return Err(Errno::EINVAL);
+        }
+        match &mut *self.node.borrow_mut() {
+            TmpNode::File(file) => file.resize(length),
+            TmpNode::Directory(_) => Err(Errno::EISDIR),
+        }
+    }
+}
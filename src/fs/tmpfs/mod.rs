@@ -0,0 +1,301 @@
+mod fd;
+
+use crate::drivers::block::DeviceId;
+use crate::fd::FDt;
+use crate::memory::heap_alloc::HEAP_SIZE;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{O_ACCMODE, O_CREAT, O_RDONLY, O_TRUNC, S_IFDIR, S_IFREG};
+use crate::utils::AsyncMutex;
+
+use super::{path, DirEntry, FSt, FileSystem, FileSystemType, FileType, Stat};
+use fd::TmpFD;
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use async_trait::async_trait;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub type TmpNodet = Arc<RefCell<TmpNode>>;
+type TmpUsaget = Arc<AtomicUsize>;
+
+// Files live on the kernel heap, each one may take a quarter of it at most,
+// and the files of one file system half of it
+pub const TMPFS_MAX_FILE_SIZE: usize = HEAP_SIZE as usize / 4;
+const TMPFS_MAX_SIZE: usize = HEAP_SIZE as usize / 2;
+
+pub enum TmpNode {
+    File(TmpFile),
+    Directory(BTreeMap<String, TmpNodet>),
+}
+
+// File contents, counted in the bytes used by its file system until dropped
+pub struct TmpFile {
+    data: Vec<u8>,
+    usage: TmpUsaget,
+}
+
+impl TmpFile {
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    // Growing past the end zero fills the file
+    pub fn resize(&mut self, len: usize) -> SysResult<()> {
+        if len > TMPFS_MAX_FILE_SIZE {
+            return Err(Errno::EFBIG);
+        }
+        let used = self.usage.load(Ordering::Relaxed) - self.data.len();
+        if len > self.data.len() {
+            if used + len > TMPFS_MAX_SIZE {
+                return Err(Errno::ENOSPC);
+            }
+            self.data
+                .try_reserve(len - self.data.len())
+                .map_err(|_| Errno::ENOSPC)?;
+        }
+        self.data.resize(len, 0);
+        self.usage.store(used + len, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Drop for TmpFile {
+    fn drop(&mut self) {
+        self.usage.fetch_sub(self.data.len(), Ordering::Relaxed);
+    }
+}
+
+impl TmpNode {
+    fn new_file(usage: &TmpUsaget) -> TmpNodet {
+        Arc::new(RefCell::new(TmpNode::File(TmpFile {
+            data: Vec::new(),
+            usage: usage.clone(),
+        })))
+    }
+
+    fn new_dir() -> TmpNodet {
+        Arc::new(RefCell::new(TmpNode::Directory(BTreeMap::new())))
+    }
+
+    pub fn file_type(&self) -> FileType {
+        match self {
+            TmpNode::File(_) => FileType::File,
+            TmpNode::Directory(_) => FileType::Directory,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        match self {
+            TmpNode::File(file) => file.data.len() as u64,
+            TmpNode::Directory(children) => children.len() as u64,
+        }
+    }
+
+    pub fn dir_entry(&self, name: &str) -> DirEntry {
+        DirEntry {
+            name: String::from(name),
+            file_type: self.file_type(),
+            size: self.size(),
+        }
+    }
+
+    // Directory listing entry at index, "." and ".." coming first
+    pub fn entry_at(&self, index: usize) -> SysResult<Option<DirEntry>> {
+        let children = match self {
+            TmpNode::Directory(children) => children,
+            TmpNode::File(_) => return Err(Errno::ENOTDIR),
+        };
+        let entry = match index {
+            0 => Some(self.dir_entry(".")),
+            1 => Some(DirEntry {
+                name: "..".to_string(),
+                file_type: FileType::Directory,
+                size: 0,
+            }),
+            _ => children
+                .iter()
+                .nth(index - 2)
+                .map(|(name, child)| child.borrow().dir_entry(name)),
+        };
+        Ok(entry)
+    }
+
+    pub fn stat(&self) -> Stat {
+        let (mode, nlink) = match self {
+            TmpNode::File(_) => (S_IFREG | 0o666, 1),
+            TmpNode::Directory(children) => {
+                let subdirs = children
+                    .values()
+                    .filter(|c| c.borrow().file_type() == FileType::Directory)
+                    .count();
+                (S_IFDIR | 0o777, 2 + subdirs as u32)
+            }
+        };
+        Stat {
+            file_type: self.file_type(),
+            size: self.size(),
+            block: 0,
+            mtime: 0,
+            mode,
+            nlink,
+            uid: 0,
+            gid: 0,
+        }
+    }
+}
+
+pub struct TmpFSType {}
+
+#[async_trait(?Send)]
+impl FileSystemType for TmpFSType {
+    fn name(&self) -> &'static str {
+        "tmpfs"
+    }
+
+    // Not backed by any device
+    async fn mount(&self, _source: Option<DeviceId>) -> SysResult<FSt> {
+        Ok(Arc::new(AsyncMutex::new(TmpFS::new())))
+    }
+}
+
+// File system living in memory only, lost on reboot
+pub struct TmpFS {
+    root: TmpNodet,
+    usage: TmpUsaget, // Bytes taken by the files, removed ones included while open
+}
+
+impl TmpFS {
+    pub fn new() -> Self {
+        TmpFS {
+            root: TmpNode::new_dir(),
+            usage: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn lookup(&self, path: &str) -> SysResult<TmpNodet> {
+        let components = path::normalize(path)?;
+        let mut node = self.root.clone();
+        for component in components {
+            let child = match &*node.borrow() {
+                TmpNode::Directory(children) => {
+                    children.get(&component).ok_or(Errno::ENOENT)?.clone()
+                }
+                TmpNode::File(_) => return Err(Errno::ENOTDIR),
+            };
+            node = child;
+        }
+        Ok(node)
+    }
+
+    // Directory holding the last component of path, along with that component
+    fn lookup_parent(&self, path: &str) -> SysResult<(TmpNodet, String)> {
+        let mut components = path::normalize(path)?;
+        // The root has no parent, and always exists
+        let name = components.pop().ok_or(Errno::EEXIST)?;
+        let parent = self.lookup(path::join(&components).as_str())?;
+        if parent.borrow().file_type() != FileType::Directory {
+            return Err(Errno::ENOTDIR);
+        }
+        Ok((parent, name))
+    }
+
+    fn create(&self, path: &str, node: TmpNodet) -> SysResult<TmpNodet> {
+        let (parent, name) = self.lookup_parent(path)?;
+        if let TmpNode::Directory(children) = &mut *parent.borrow_mut() {
+            if children.contains_key(&name) {
+                return Err(Errno::EEXIST);
+            }
+            children.insert(name, node.clone());
+        }
+        Ok(node)
+    }
+
+    fn remove(&self, path: &str, file_type: FileType) -> SysResult<()> {
+        let (parent, name) = self.lookup_parent(path)?;
+        let mut parent = parent.borrow_mut();
+        let children = match &mut *parent {
+            TmpNode::Directory(children) => children,
+            TmpNode::File(_) => return Err(Errno::ENOTDIR),
+        };
+
+        match &*children.get(&name).ok_or(Errno::ENOENT)?.borrow() {
+            TmpNode::Directory(grandchildren) if file_type == FileType::Directory => {
+                if !grandchildren.is_empty() {
+                    return Err(Errno::ENOTEMPTY);
+                }
+            }
+            TmpNode::File(_) if file_type == FileType::File => {}
+            TmpNode::Directory(_) => return Err(Errno::EISDIR),
+            TmpNode::File(_) => return Err(Errno::ENOTDIR),
+        }
+        children.remove(&name);
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl FileSystem for TmpFS {
+    async fn open(&mut self, path: &str, flags: u32) -> SysResult<FDt> {
+        let node = match self.lookup(path) {
+            Ok(node) => node,
+            Err(Errno::ENOENT) if flags & O_CREAT != 0 => {
+                self.create(path, TmpNode::new_file(&self.usage))?
+            }
+            Err(err) => return Err(err),
+        };
+
+        if let TmpNode::File(file) = &mut *node.borrow_mut() {
+            if flags & O_TRUNC != 0 && flags & O_ACCMODE != O_RDONLY {
+                file.resize(0)?;
+            }
+        } else if flags & O_ACCMODE != O_RDONLY {
+            return Err(Errno::EISDIR);
+        }
+
+        Ok(TmpFD::new(node, flags).await)
+    }
+
+    async fn readdir(&mut self, path: &str) -> SysResult<Vec<DirEntry>> {
+        let node = self.lookup(path)?;
+        let node = node.borrow();
+        let mut entries = Vec::new();
+        while let Some(entry) = node.entry_at(entries.len())? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    async fn stat(&mut self, path: &str) -> SysResult<Stat> {
+        let node = self.lookup(path)?;
+        let stat = node.borrow().stat();
+        Ok(stat)
+    }
+
+    // No symbolic links can be created
+    async fn readlink(&mut self, path: &str) -> SysResult<String> {
+        self.lookup(path)?;
+        Err(Errno::EINVAL)
+    }
+
+    async fn mkdir(&mut self, path: &str) -> SysResult<()> {
+        self.create(path, TmpNode::new_dir()).map(|_| ())
+    }
+
+    async fn unlink(&mut self, path: &str) -> SysResult<()> {
+        self.remove(path, FileType::File)
+    }
+
+    async fn rmdir(&mut self, path: &str) -> SysResult<()> {
+        self.remove(path, FileType::Directory)
+    }
+}
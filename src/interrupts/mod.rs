@@ -1,6 +1,6 @@
 use crate::drivers::vga::{self, Color, ColorCode};
 use crate::hlt_loop;
-use crate::memory::gdt;
+use crate::memory::{gdt, mmap};
 use crate::println;
 
 use core::arch::asm;
@@ -87,16 +87,22 @@ extern "x86-interrupt" fn syscall_handler_32(_stack_frame: InterruptStackFrame)
         )
     }
     println!("Received syscall");
-    crate::syscalls::syscall_routine(rax);
+    crate::syscalls::syscall_routine(rax, [rbx, rcx, rdx]);
 }
 
 extern "x86-interrupt" fn page_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: PageFaultErrorCode,
 ) {
+    use x86_64::registers::control::Cr2;
+
+    // Pages of file mappings are read on first access
+    if mmap::handle_page_fault(Cr2::read(), error_code) {
+        return;
+    }
+
     let color: vga::ColorCode = vga::get_color();
     vga::change_color(ColorCode::new(Color::LightRed, Color::Black));
-    use x86_64::registers::control::Cr2;
 
     println!("EXCEPTION: PAGE FAULT");
     println!("Accessed Address: {:?}", Cr2::read());
@@ -1,9 +1,19 @@
 use super::{InterruptIndex, PICS};
+
+use alloc::vec::Vec;
+use core::task::Waker;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts;
 use x86_64::structures::idt::InterruptStackFrame;
 
 static mut TICKS: u64 = 0;
 
-#[allow(dead_code)]
+lazy_static! {
+    // Tasks to wake on the next timer interrupt
+    static ref TICK_WAKERS: Mutex<Vec<Waker>> = Mutex::new(Vec::new());
+}
+
 pub fn gettick() -> u64 {
     unsafe { return TICKS }
 }
@@ -12,7 +22,23 @@ pub extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptSta
     // TODO: thread preemption
     unsafe {
         TICKS += 1;
+        // Registration happens with interrupts disabled, the lock is free here
+        if let Some(mut wakers) = TICK_WAKERS.try_lock() {
+            for waker in wakers.drain(..) {
+                waker.wake();
+            }
+        }
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
     }
 }
+
+// Wake the task once, on the next tick
+pub fn wake_on_tick(waker: &Waker) {
+    interrupts::without_interrupts(|| {
+        let mut wakers = TICK_WAKERS.lock();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    });
+}
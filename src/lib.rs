@@ -17,9 +17,11 @@ mod utils;
 extern crate alloc;
 extern crate multiboot2;
 
+use crate::fd::FDt;
 use crate::fs::FileSystem;
 use core::panic::PanicInfo;
 use drivers::vga::{self, Color, ColorCode};
+use futures_util::future;
 use multiboot2::BootInformation;
 use task::{executor::EXECUTOR, keyboard, Task};
 
@@ -48,6 +50,7 @@ pub fn init(boot_info: &BootInformation) {
     vga::change_color(ColorCode::new(Color::LightCyan, Color::Black));
     println!("Starting init");
     memory::init(boot_info);
+    fs::initramfs::init(boot_info);
     memory::gdt::init_gdt();
     interrupts::init_idt();
     vga::change_color(ColorCode::new(Color::LightGreen, Color::Black));
@@ -62,9 +65,13 @@ pub extern "C" fn julios_main(multiboot_info_addr: usize) -> ! {
     serial_println!("Hello serial");
 
     let mut executor = EXECUTOR.try_lock().unwrap();
-    executor.spawn(Task::new(drivers::atapi::init()));
-    executor.spawn(Task::new(keyboard::print_keypresses()));
-    executor.spawn(Task::new(get_file()));
+    executor.spawn(Task::new(async {
+        // The cdrom is registered once the drive answered, before mounting from it
+        drivers::atapi::init().await;
+        fs::init().await;
+        // Both open files, once the file systems are mounted
+        future::join(keyboard::print_keypresses(), get_file()).await;
+    }));
     executor.spawn(Task::new(proc::scheduler::scheduler_run()));
 
     EXECUTOR.force_unlock(); // Ouioui t'inquietes
@@ -73,24 +80,16 @@ pub extern "C" fn julios_main(multiboot_info_addr: usize) -> ! {
 }
 
 async fn get_file() {
+    let path = "/mnt/iso//boot/../boot/grub/./grub.cfg";
     let fd = fs::VIRTUAL_FS
         .lock()
         .await
-        .open("/mnt/iso//boot/grub//grub.cfg", syscalls::io::O_RDONLY)
-        .await
-        .unwrap();
-    let mut buf: [u8; 100] = [0; 100];
-    let read = fd.borrow_mut().read(&mut buf, 100).await;
-
-    serial_println!("{:?}", read);
-    serial_println!("{}", alloc::str::from_utf8(&buf).unwrap());
-
-    fd.borrow_mut().lseek(10, syscalls::io::SEEK_SET).await;
-
-    fd.borrow_mut().read(&mut buf, 100).await;
-    serial_println!("{}", alloc::str::from_utf8(&buf).unwrap());
-
-    fd.borrow_mut().close().await;
+        .open(path, syscalls::io::O_RDONLY)
+        .await;
+    match fd {
+        Ok(fd) => print_file(fd).await,
+        Err(err) => println!("Could not open {}: {}", path, err),
+    }
 
     let thread = Arc::new(RefCell::new(proc::thread::Thread::new(
         proc::thread::routine as u64,
@@ -100,3 +99,21 @@ async fn get_file() {
         .await
         .register(thread.clone());
 }
+
+async fn print_file(fd: FDt) {
+    let mut buf: [u8; 100] = [0; 100];
+    let read = fd.lock().await.read(&mut buf, 100).await;
+    if let Ok(read) = read {
+        serial_println!("{}", alloc::str::from_utf8(&buf[..read]).unwrap_or("?"));
+    }
+
+    let seek = fd.lock().await.lseek(10, syscalls::io::SEEK_SET).await;
+    if seek.is_ok() {
+        let read = fd.lock().await.read(&mut buf, 100).await;
+        if let Ok(read) = read {
+            serial_println!("{}", alloc::str::from_utf8(&buf[..read]).unwrap_or("?"));
+        }
+    }
+
+    fd.lock().await.close().await;
+}
@@ -1,4 +1,5 @@
 pub use super::PAGE_SIZE;
+use alloc::vec::Vec;
 use multiboot2::{MemoryArea, MemoryAreaIter};
 pub use x86_64::structures::paging::{
     frame::PhysFrame as Frame, FrameAllocator, FrameDeallocator, Size4KiB,
@@ -13,14 +14,21 @@ pub struct AreaFrameAllocator {
     kernel_end: Frame,
     multiboot_start: Frame,
     multiboot_end: Frame,
+    module: Option<(Frame, Frame)>, // Range of the boot loader module, if any
+    allocated: u64,                 // Number of frames handed out
+    freed: Vec<Frame>,              // Frames given back, handed out first
 }
 
+// The memory areas are only read, from the multiboot structure which is never freed
+unsafe impl Send for AreaFrameAllocator {}
+
 impl AreaFrameAllocator {
     pub fn new(
         kernel_start: u64,
         kernel_end: u64,
         multiboot_start: u64,
         multiboot_end: u64,
+        module: Option<(u64, u64)>,
         memory_areas: MemoryAreaIter,
     ) -> AreaFrameAllocator {
         let mut allocator = AreaFrameAllocator {
@@ -31,6 +39,14 @@ impl AreaFrameAllocator {
             kernel_end: Frame::containing_address(PhysAddr::new(kernel_end)),
             multiboot_start: Frame::containing_address(PhysAddr::new(multiboot_start)),
             multiboot_end: Frame::containing_address(PhysAddr::new(multiboot_end)),
+            module: module.map(|(start, end)| {
+                (
+                    Frame::containing_address(PhysAddr::new(start)),
+                    Frame::containing_address(PhysAddr::new(end)),
+                )
+            }),
+            allocated: 0,
+            freed: Vec::new(),
         };
         allocator.choose_next_area();
         allocator
@@ -53,10 +69,41 @@ impl AreaFrameAllocator {
             }
         }
     }
+
+    pub fn allocated_frames(&self) -> u64 {
+        self.allocated
+    }
+
+    pub fn next_free_frame(&self) -> Frame {
+        Frame::containing_address(self.next_free_frame.start_address())
+    }
+
+    // Base address and length of the area frames are taken from
+    pub fn current_area(&self) -> Option<(u64, u64)> {
+        self.current_area.map(|area| (area.base_addr, area.length))
+    }
+
+    // Frames not handed out yet, the kernel, multiboot and module ranges included
+    pub fn free_frames(&self) -> u64 {
+        let next_free = self.next_free_frame.start_address().as_u64();
+        self.areas
+            .clone()
+            .map(|area| {
+                let start = core::cmp::max(area.base_addr, next_free);
+                let end = area.base_addr + area.length;
+                end.saturating_sub(start) / PAGE_SIZE as u64
+            })
+            .sum::<u64>()
+            + self.freed.len() as u64
+    }
 }
 
 unsafe impl FrameAllocator<Size4KiB> for AreaFrameAllocator {
     fn allocate_frame(&mut self) -> Option<Frame> {
+        if let Some(frame) = self.freed.pop() {
+            self.allocated += 1;
+            return Some(frame);
+        }
         if let Some(area) = self.current_area {
             // "Clone" the frame to return it if it's free. Frame doesn't
             // implement Clone, but we can construct an identical frame.
@@ -79,10 +126,18 @@ unsafe impl FrameAllocator<Size4KiB> for AreaFrameAllocator {
                 // `frame` is used by the multiboot information structure
                 self.next_free_frame =
                     Frame::containing_address(self.multiboot_end.start_address() + PAGE_SIZE);
+            } else if let Some((_, module_end)) = self
+                .module
+                .filter(|(start, end)| frame >= *start && frame <= *end)
+            {
+                // `frame` holds the module loaded by the boot loader
+                self.next_free_frame =
+                    Frame::containing_address(module_end.start_address() + PAGE_SIZE);
             } else {
                 // frame is unused, increment `next_free_frame` and return it
                 self.next_free_frame =
                     Frame::containing_address(self.next_free_frame.start_address() + PAGE_SIZE);
+                self.allocated += 1;
                 return Some(frame);
             }
             // `frame` was not valid, try it again with the updated `next_free_frame`
@@ -94,8 +149,11 @@ unsafe impl FrameAllocator<Size4KiB> for AreaFrameAllocator {
 }
 
 impl FrameDeallocator<Size4KiB> for AreaFrameAllocator {
-    unsafe fn deallocate_frame(&mut self, _frame: Frame) {
-        unimplemented!()
+    // The list only grows once the heap is initialized, as frames are
+    // deallocated when unmapping pages
+    unsafe fn deallocate_frame(&mut self, frame: Frame) {
+        self.allocated -= 1;
+        self.freed.push(frame);
     }
 }
 
@@ -0,0 +1,370 @@
+use super::paging::{get_active_page_table, Flags, FrameAllocator, Mapper, Page, Size4KiB};
+use super::{FRAME_ALLOCATOR, PAGE_SIZE};
+use crate::fd::table::{release, OpenFile};
+use crate::fs::FileType;
+use crate::syscalls::errno::{Errno, SysResult};
+use crate::syscalls::io::{
+    MAP_FIXED, MAP_PRIVATE, MAP_SHARED, O_ACCMODE, O_RDWR, O_WRONLY, PROT_EXEC, PROT_NONE,
+    PROT_READ, PROT_WRITE,
+};
+use crate::task::block_on::block_on;
+use crate::utils::AsyncMutex;
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use lazy_static::lazy_static;
+use x86_64::structures::idt::PageFaultErrorCode;
+use x86_64::structures::paging::mapper::{Translate, TranslateResult};
+use x86_64::structures::paging::FrameDeallocator;
+use x86_64::VirtAddr;
+
+// Virtual addresses handed out to file mappings
+const MMAP_START: u64 = 0x5555_0000_0000;
+const MMAP_END: u64 = 0x5556_0000_0000;
+
+lazy_static! {
+    // Mappings by start address, their pages are mapped on first access
+    static ref MAPPINGS: AsyncMutex<BTreeMap<u64, Mapping>> = AsyncMutex::new(BTreeMap::new());
+}
+
+#[derive(Clone)]
+struct Mapping {
+    start: u64,
+    len: u64,            // Multiple of the page size
+    file: Arc<OpenFile>, // Kept open until the whole mapping is removed
+    offset: u64,         // File offset of the first page
+    prot: u32,
+    shared: bool, // Written pages go back to the file
+}
+
+impl Mapping {
+    fn end(&self) -> u64 {
+        self.start + self.len
+    }
+
+    fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.end()
+    }
+
+    fn flags(&self) -> Flags {
+        let mut flags = Flags::PRESENT;
+        if self.prot & PROT_WRITE != 0 {
+            flags |= Flags::WRITABLE;
+        }
+        if self.prot & PROT_EXEC == 0 {
+            flags |= Flags::NO_EXECUTE;
+        }
+        flags
+    }
+
+    // Part of the mapping from start to end, both inside it
+    fn slice(&self, start: u64, end: u64) -> Mapping {
+        Mapping {
+            start,
+            len: end - start,
+            file: self.file.clone(),
+            offset: self.offset + (start - self.start),
+            prot: self.prot,
+            shared: self.shared,
+        }
+    }
+}
+
+fn page_align(len: u64) -> u64 {
+    (len + PAGE_SIZE as u64 - 1) & !(PAGE_SIZE as u64 - 1)
+}
+
+fn pages(start: u64, end: u64) -> impl Iterator<Item = Page<Size4KiB>> {
+    (start..end)
+        .step_by(PAGE_SIZE)
+        .map(|addr| Page::containing_address(VirtAddr::new(addr)))
+}
+
+fn page_slice(page: Page<Size4KiB>, len: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(page.start_address().as_mut_ptr::<u8>(), len) }
+}
+
+// Lowest free range of len bytes, between the existing mappings
+fn find_free(mappings: &BTreeMap<u64, Mapping>, len: u64) -> SysResult<u64> {
+    let mut start = MMAP_START;
+    for mapping in mappings.values() {
+        if start + len <= mapping.start {
+            break;
+        }
+        start = mapping.end();
+    }
+    match start + len <= MMAP_END {
+        true => Ok(start),
+        false => Err(Errno::ENOMEM),
+    }
+}
+
+// Map len bytes of a regular file from offset, returning the start address.
+// Private mappings never write back, shared writable ones need a descriptor
+// open for reading and writing. Pages are read on first access.
+pub async fn mmap(
+    file: Arc<OpenFile>,
+    len: u64,
+    prot: u32,
+    flags: u32,
+    offset: u64,
+) -> SysResult<u64> {
+    if len == 0 || !offset.is_multiple_of(PAGE_SIZE as u64) || flags & MAP_FIXED != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return Err(Errno::EINVAL),
+    };
+    if file.fd.lock().await.fstat().await?.file_type != FileType::File {
+        return Err(Errno::ENODEV);
+    }
+    // Read only file systems refuse to open files for writing
+    let access = file.flags() & O_ACCMODE;
+    if access == O_WRONLY || (shared && prot & PROT_WRITE != 0 && access != O_RDWR) {
+        return Err(Errno::EACCES);
+    }
+
+    let len = page_align(len);
+    let mut mappings = MAPPINGS.lock().await;
+    let start = find_free(&mappings, len)?;
+    mappings.insert(
+        start,
+        Mapping {
+            start,
+            len,
+            file,
+            offset,
+            prot,
+            shared,
+        },
+    );
+    Ok(start)
+}
+
+// Remove the mappings in the range, writing shared pages back first
+pub async fn munmap(addr: u64, len: u64) -> SysResult<()> {
+    if !addr.is_multiple_of(PAGE_SIZE as u64) || len == 0 {
+        return Err(Errno::EINVAL);
+    }
+    let end = addr + page_align(len);
+    let removed: Vec<Mapping> = {
+        let mut mappings = MAPPINGS.lock().await;
+        let starts: Vec<u64> = mappings
+            .range(..end)
+            .filter(|(_, mapping)| mapping.end() > addr)
+            .map(|(start, _)| *start)
+            .collect();
+        starts
+            .iter()
+            .filter_map(|start| mappings.remove(start))
+            .collect()
+    };
+
+    let mut res = Ok(());
+    for mapping in removed {
+        let from = core::cmp::max(addr, mapping.start);
+        let to = core::cmp::min(end, mapping.end());
+        if let Err(err) = write_back(&mapping, from, to).await {
+            res = Err(err);
+        }
+        unmap_pages(from, to);
+
+        // What is left on each side stays mapped
+        let mut mappings = MAPPINGS.lock().await;
+        if mapping.start < from {
+            mappings.insert(mapping.start, mapping.slice(mapping.start, from));
+        }
+        if to < mapping.end() {
+            mappings.insert(to, mapping.slice(to, mapping.end()));
+        }
+        drop(mappings);
+        release(mapping.file).await;
+    }
+    res
+}
+
+// Write the modified pages of the shared mappings in the range back
+pub async fn msync(addr: u64, len: u64) -> SysResult<()> {
+    if !addr.is_multiple_of(PAGE_SIZE as u64) {
+        return Err(Errno::EINVAL);
+    }
+    let end = addr + page_align(len);
+    let synced: Vec<Mapping> = MAPPINGS
+        .lock()
+        .await
+        .range(..end)
+        .map(|(_, mapping)| mapping)
+        .filter(|mapping| mapping.end() > addr)
+        .cloned()
+        .collect();
+    if synced.is_empty() {
+        return Err(Errno::ENOMEM);
+    }
+    for mapping in synced {
+        let from = core::cmp::max(addr, mapping.start);
+        let to = core::cmp::min(end, mapping.end());
+        write_back(&mapping, from, to).await?;
+    }
+    Ok(())
+}
+
+// Pages are dirty once written to, they are clean again after the write
+async fn write_back(mapping: &Mapping, from: u64, to: u64) -> SysResult<()> {
+    if !mapping.shared || mapping.prot & PROT_WRITE == 0 {
+        return Ok(());
+    }
+    let mut table = get_active_page_table();
+    // Mappings do not extend the file, the end of the last page is dropped
+    let size = mapping.file.fd.lock().await.fstat().await?.size;
+    for page in pages(from, to) {
+        let dirty = match table.translate(page.start_address()) {
+            TranslateResult::Mapped { flags, .. } => flags.contains(Flags::DIRTY),
+            _ => false,
+        };
+        if !dirty {
+            continue;
+        }
+        let offset = mapping.offset + (page.start_address().as_u64() - mapping.start);
+        if offset < size {
+            let len = core::cmp::min(PAGE_SIZE as u64, size - offset) as usize;
+            let written = mapping
+                .file
+                .fd
+                .lock()
+                .await
+                .pwrite(page_slice(page, len), len, offset)
+                .await?;
+            if written < len {
+                return Err(Errno::EIO);
+            }
+        }
+        if let Ok(flush) = unsafe { table.update_flags(page, mapping.flags()) } {
+            flush.flush();
+        }
+    }
+    Ok(())
+}
+
+// The frames of the pages read so far are freed
+fn unmap_pages(from: u64, to: u64) {
+    let mut table = get_active_page_table();
+    let mut allocator = FRAME_ALLOCATOR.lock();
+    for page in pages(from, to) {
+        if let Ok((frame, flush)) = table.unmap(page) {
+            flush.flush();
+            if let Some(allocator) = allocator.as_mut() {
+                unsafe { allocator.deallocate_frame(frame) };
+            }
+        }
+    }
+}
+
+// Mapping holding addr
+fn find_mapping(mappings: &BTreeMap<u64, Mapping>, addr: u64) -> SysResult<Mapping> {
+    mappings
+        .range(..=addr)
+        .next_back()
+        .map(|(_, mapping)| mapping)
+        .filter(|mapping| mapping.contains(addr))
+        .cloned()
+        .ok_or(Errno::EFAULT)
+}
+
+// Read the page holding addr from the file, in a new frame. A descriptor
+// in use by another task is not waited for.
+async fn fault_in(mapping: &Mapping, addr: u64, write: bool) -> SysResult<()> {
+    if mapping.prot & (PROT_READ | PROT_WRITE | PROT_EXEC) == PROT_NONE
+        || (write && mapping.prot & PROT_WRITE == 0)
+    {
+        return Err(Errno::EACCES);
+    }
+
+    // Mapped writable to be filled, then with the protection of the mapping
+    let page = Page::<Size4KiB>::containing_address(VirtAddr::new(addr));
+    let mut table = get_active_page_table();
+    {
+        let mut allocator = FRAME_ALLOCATOR.lock();
+        let allocator = allocator.as_mut().ok_or(Errno::ENOMEM)?;
+        let frame = allocator.allocate_frame().ok_or(Errno::ENOMEM)?;
+        let flags = Flags::PRESENT | Flags::WRITABLE | Flags::NO_EXECUTE;
+        unsafe { table.map_to(page, frame, flags, allocator) }
+            .map_err(|_| Errno::ENOMEM)?
+            .flush();
+    }
+
+    // The end of the last page, past the end of the file, is zero filled
+    let data = page_slice(page, PAGE_SIZE);
+    let mut offset = mapping.offset + (page.start_address().as_u64() - mapping.start);
+    let mut read: usize = 0;
+    while read < PAGE_SIZE {
+        let buf = &mut data[read..];
+        let len = buf.len();
+        let res = match mapping.file.fd.try_lock() {
+            Some(mut fd) => fd.pread(buf, len, offset).await,
+            None => Err(Errno::EBUSY),
+        };
+        match res {
+            Ok(0) => break,
+            Ok(n) => {
+                read += n;
+                offset += n as u64;
+            }
+            Err(err) => {
+                let start = page.start_address().as_u64();
+                unmap_pages(start, start + PAGE_SIZE as u64);
+                return Err(err);
+            }
+        }
+    }
+    data[read..].fill(0);
+
+    // Replacing the flags also clears the dirty bit set while filling the page
+    unsafe { table.update_flags(page, mapping.flags()) }
+        .map_err(|_| Errno::EFAULT)?
+        .flush();
+    Ok(())
+}
+
+// Read the unmapped pages of a buffer a syscall accesses, before it locks
+// the descriptor or the devices these reads need
+pub async fn prefault(addr: u64, len: u64, write: bool) -> SysResult<()> {
+    let start = core::cmp::max(addr & !(PAGE_SIZE as u64 - 1), MMAP_START);
+    let end = core::cmp::min(addr.saturating_add(len), MMAP_END);
+    for page in pages(start, end) {
+        let mapped = matches!(
+            get_active_page_table().translate(page.start_address()),
+            TranslateResult::Mapped { .. }
+        );
+        if !mapped {
+            let addr = page.start_address().as_u64();
+            let mapping = find_mapping(&*MAPPINGS.lock().await, addr)?;
+            fault_in(&mapping, addr, write).await?;
+        }
+    }
+    Ok(())
+}
+
+// Resolve a fault on a file mapping, false if the address is not mapped
+// or the access is not allowed. The executor tasks do not run meanwhile,
+// locks held by the faulting task fail the fault rather than being waited
+// for: syscalls prefault their buffers not to access them with locks held.
+pub fn handle_page_fault(addr: VirtAddr, error_code: PageFaultErrorCode) -> bool {
+    let addr = addr.as_u64();
+    if !(MMAP_START..MMAP_END).contains(&addr) {
+        return false;
+    }
+    // The page is present, the access breaks the mapping protection
+    if error_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
+        return false;
+    }
+    let write = error_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE);
+    let mapping = match MAPPINGS.try_lock() {
+        Some(mappings) => find_mapping(&mappings, addr),
+        None => return false,
+    };
+    match mapping {
+        Ok(mapping) => block_on(fault_in(&mapping, addr, write)).is_ok(),
+        Err(_) => false,
+    }
+}
@@ -1,25 +1,34 @@
 pub use self::frame_allocator::AreaFrameAllocator;
 use crate::println;
 use heap_alloc::{ALLOCATOR, HEAP_SIZE, HEAP_START};
+use lazy_static::lazy_static;
 use multiboot2::BootInformation;
 pub use paging::kernel_remap;
 use paging::{Flags, FrameAllocator, Mapper, Page, RecursivePageTable, Size4KiB};
+use spin::Mutex;
 use x86_64::structures::paging::{mapper::MapToError, page::PageRangeInclusive};
 use x86_64::VirtAddr;
 
 pub mod frame_allocator;
 pub mod gdt;
 pub mod heap_alloc;
+pub mod mmap;
 pub mod paging;
 
 pub const PAGE_SIZE: usize = 4096;
 
+lazy_static! {
+    // Frame allocator used during the initialization, kept for later allocations
+    pub static ref FRAME_ALLOCATOR: Mutex<Option<AreaFrameAllocator>> = Mutex::new(None);
+}
+
 pub fn init(boot_info: &BootInformation) {
     enable_nxe_bit();
     enable_write_protect_bit();
     let mut frame_allocator = get_frame_allocator(boot_info.start_address());
     let mut active_table = kernel_remap(&mut frame_allocator, boot_info);
     init_heap(&mut active_table, &mut frame_allocator).expect("Heap initialization failed");
+    *FRAME_ALLOCATOR.lock() = Some(frame_allocator);
 }
 
 fn init_heap<A>(
@@ -76,11 +85,16 @@ fn get_frame_allocator(multiboot_info_addr: usize) -> AreaFrameAllocator {
     let multiboot_start: u64 = multiboot_info_addr as u64;
     let multiboot_end: u64 = multiboot_start + (boot_info.total_size as u64);
 
+    let module = boot_info
+        .module_tag()
+        .map(|m| (m.start_address() as u64, m.end_address() as u64));
+
     AreaFrameAllocator::new(
         kernel_start,
         kernel_end,
         multiboot_start,
         multiboot_end,
+        module,
         memory_map_tag.memory_areas(),
     )
 }
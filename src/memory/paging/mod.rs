@@ -100,6 +100,29 @@ where
                     .flush();
             }
         }
+
+        // The module is read in place, as the initramfs
+        if let Some(module) = boot_info
+            .module_tag()
+            .filter(|m| m.end_address() > m.start_address())
+        {
+            let module_start =
+                Frame::<Size4KiB>::containing_address(PhysAddr::new(module.start_address() as u64));
+            let module_end =
+                Frame::containing_address(PhysAddr::new(module.end_address() as u64 - 1));
+            for frame in Frame::range_inclusive(module_start, module_end) {
+                // A page may be shared with the multiboot info struct
+                if frame >= multiboot_start && frame <= multiboot_end {
+                    continue;
+                }
+                unsafe {
+                    mapper
+                        .identity_map(frame, Flags::PRESENT | Flags::NO_EXECUTE, allocator)
+                        .expect("Failed to identity map boot module")
+                        .flush();
+                }
+            }
+        }
     });
 
     let old_table = new_table.activate();
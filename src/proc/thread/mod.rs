@@ -9,7 +9,7 @@ use core::sync::atomic::{AtomicU64, Ordering};
 use alloc::alloc::{alloc, dealloc, Layout};
 use lazy_static::lazy_static;
 
-const STACK_SIZE: usize = 4096 * 20;
+pub const STACK_SIZE: usize = 4096 * 20;
 
 lazy_static! {
     pub static ref RUNNING_THREAD: AsyncMutex<ThreadId> = AsyncMutex::new(ThreadId(0));
@@ -39,7 +39,7 @@ pub fn resume_k_thread() {
 
 pub fn routine() {
     println!("Routine executed");
-    crate::syscalls::syscall_routine(crate::syscalls::EXIT_ID); // Call exit
+    crate::syscalls::syscall_routine(crate::syscalls::EXIT_ID, [0; 3]); // Call exit
     println!("SHOULD NEVER BE DISPLAYED");
 }
 
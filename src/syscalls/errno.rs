@@ -0,0 +1,92 @@
+use crate::fs::path::PathError;
+
+use core::fmt;
+
+pub type SysResult<T> = Result<T, Errno>;
+
+// POSIX error numbers, with their Linux values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum Errno {
+    EPERM = 1,         // Operation not permitted
+    ENOENT = 2,        // No such file or directory
+    EIO = 5,           // I/O error
+    ENXIO = 6,         // No such device or address
+    EBADF = 9,         // Bad file descriptor
+    ENOMEM = 12,       // Out of memory
+    EACCES = 13,       // Permission denied
+    EFAULT = 14,       // Bad address
+    EBUSY = 16,        // Device or resource busy
+    EEXIST = 17,       // File exists
+    ENODEV = 19,       // No such device
+    ENOTDIR = 20,      // Not a directory
+    EISDIR = 21,       // Is a directory
+    EINVAL = 22,       // Invalid argument
+    EMFILE = 24,       // Too many open files
+    EFBIG = 27,        // File too large
+    ENOSPC = 28,       // No space left on device
+    ESPIPE = 29,       // Illegal seek
+    EROFS = 30,        // Read-only file system
+    EPIPE = 32,        // Broken pipe
+    ENAMETOOLONG = 36, // File name too long
+    ENOSYS = 38,       // Function not implemented
+    ENOTEMPTY = 39,    // Directory not empty
+}
+
+impl Errno {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Errno::EPERM => "operation not permitted",
+            Errno::ENOENT => "no such file or directory",
+            Errno::EIO => "input/output error",
+            Errno::ENXIO => "no such device or address",
+            Errno::EBADF => "bad file descriptor",
+            Errno::ENOMEM => "cannot allocate memory",
+            Errno::EACCES => "permission denied",
+            Errno::EFAULT => "bad address",
+            Errno::EBUSY => "device or resource busy",
+            Errno::EEXIST => "file exists",
+            Errno::ENODEV => "no such device",
+            Errno::ENOTDIR => "not a directory",
+            Errno::EISDIR => "is a directory",
+            Errno::EINVAL => "invalid argument",
+            Errno::EMFILE => "too many open files",
+            Errno::EFBIG => "file too large",
+            Errno::ENOSPC => "no space left on device",
+            Errno::ESPIPE => "illegal seek",
+            Errno::EROFS => "read-only file system",
+            Errno::EPIPE => "broken pipe",
+            Errno::ENAMETOOLONG => "file name too long",
+            Errno::ENOSYS => "function not implemented",
+            Errno::ENOTEMPTY => "directory not empty",
+        }
+    }
+
+    // Value returned to user programs, in the syscall result register
+    pub fn to_syscall_result(&self) -> u64 {
+        -(*self as i64) as u64
+    }
+}
+
+impl fmt::Display for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+impl From<PathError> for Errno {
+    fn from(err: PathError) -> Self {
+        match err {
+            PathError::NotAbsolute => Errno::EINVAL,
+            PathError::NoMountPoint => Errno::ENOENT,
+        }
+    }
+}
+
+// Successful results are returned as is, failures as negative errno values
+pub fn syscall_result(res: SysResult<u64>) -> u64 {
+    match res {
+        Ok(value) => value,
+        Err(errno) => errno.to_syscall_result(),
+    }
+}
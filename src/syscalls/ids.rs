@@ -1,3 +1,27 @@
 pub type SyscallId = u64;
 
 pub const EXIT_ID: SyscallId = 0;
+pub const READ_ID: SyscallId = 1;
+pub const WRITE_ID: SyscallId = 2;
+pub const OPEN_ID: SyscallId = 3;
+pub const CLOSE_ID: SyscallId = 4;
+pub const LSEEK_ID: SyscallId = 5;
+pub const DUP_ID: SyscallId = 6;
+pub const DUP2_ID: SyscallId = 7;
+pub const FCNTL_ID: SyscallId = 8;
+pub const PIPE_ID: SyscallId = 9;
+pub const POLL_ID: SyscallId = 10;
+pub const MMAP_ID: SyscallId = 11;
+pub const MUNMAP_ID: SyscallId = 12;
+pub const MSYNC_ID: SyscallId = 13;
+pub const UMOUNT_ID: SyscallId = 14;
+pub const GETDENTS_ID: SyscallId = 15;
+pub const STAT_ID: SyscallId = 16;
+pub const FSTAT_ID: SyscallId = 17;
+pub const READLINK_ID: SyscallId = 18;
+pub const MKDIR_ID: SyscallId = 19;
+pub const UNLINK_ID: SyscallId = 20;
+pub const RMDIR_ID: SyscallId = 21;
+pub const FTRUNCATE_ID: SyscallId = 22;
+pub const READV_ID: SyscallId = 23;
+pub const WRITEV_ID: SyscallId = 24;
@@ -1,7 +1,547 @@
+use crate::fd::pipe;
+use crate::fd::poll::{self, PollFd};
+use crate::fd::table::{process_fd_table, release, OpenFile};
+use crate::fs::{self, DirEntry, FileSystem, FileType, Stat, VIRTUAL_FS};
+use crate::memory::mmap;
+
+use super::errno::{Errno, SysResult};
+use super::SyscallContext;
+
+use alloc::{sync::Arc, vec::Vec};
+use core::convert::TryFrom;
+
 // open flags
 pub const O_RDONLY: u32 = 0;
+pub const O_WRONLY: u32 = 1;
+pub const O_RDWR: u32 = 2;
+pub const O_ACCMODE: u32 = 3; // Access mode mask
+pub const O_CREAT: u32 = 0o100;
+pub const O_TRUNC: u32 = 0o1000;
+pub const O_APPEND: u32 = 0o2000;
+pub const O_CLOEXEC: u32 = 0o2000000;
+
+// fcntl commands
+pub const F_DUPFD: u64 = 0;
+pub const F_GETFD: u64 = 1;
+pub const F_SETFD: u64 = 2;
+pub const F_GETFL: u64 = 3;
+pub const F_SETFL: u64 = 4;
+pub const F_DUPFD_CLOEXEC: u64 = 1030;
+
+// descriptor flags
+pub const FD_CLOEXEC: u64 = 1;
+
+// mmap protection and flags
+pub const PROT_NONE: u32 = 0;
+pub const PROT_READ: u32 = 1;
+pub const PROT_WRITE: u32 = 2;
+pub const PROT_EXEC: u32 = 4;
+pub const MAP_SHARED: u32 = 1;
+pub const MAP_PRIVATE: u32 = 2;
+pub const MAP_FIXED: u32 = 0x10;
 
 // seek flags
 pub const SEEK_SET: u32 = 0;
 pub const SEEK_CUR: u32 = 1;
 pub const SEEK_END: u32 = 2;
+
+// poll events
+pub const POLLIN: u16 = 0x1; // Data to read
+pub const POLLOUT: u16 = 0x4; // Writing does not block
+pub const POLLERR: u16 = 0x8; // Error condition, always reported
+pub const POLLHUP: u16 = 0x10; // Other end closed, always reported
+pub const POLLNVAL: u16 = 0x20; // Descriptor not open, always reported
+
+// getdents entry types
+pub const DT_FIFO: u8 = 1;
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_BLK: u8 = 6;
+pub const DT_REG: u8 = 8;
+pub const DT_LNK: u8 = 10;
+
+// file mode bits
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFLNK: u32 = 0o120000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFBLK: u32 = 0o060000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFIFO: u32 = 0o010000;
+
+// Descriptor numbers, pointers and lengths are passed in the syscall arguments
+pub async fn read(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, buf, count] = context.args;
+    let buf = user_slice_mut(buf, count)?;
+    mmap::prefault(buf.as_ptr() as u64, count, true).await?;
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow()
+        .get(fd)?;
+    let read = file.read(buf, count as usize).await?;
+    Ok(read as u64)
+}
+
+pub async fn write(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, buf, count] = context.args;
+    let buf = user_slice(buf, count)?;
+    mmap::prefault(buf.as_ptr() as u64, count, false).await?;
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow()
+        .get(fd)?;
+    let written = file.write(buf, count as usize).await?;
+    Ok(written as u64)
+}
+
+pub async fn readv(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, iov, iovcnt] = context.args;
+    let mut bufs = Vec::new();
+    for iovec in user_iovecs(iov, iovcnt).await? {
+        let buf = user_slice_mut(iovec.base, iovec.len)?;
+        mmap::prefault(iovec.base, iovec.len, true).await?;
+        bufs.push(buf);
+    }
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow()
+        .get(fd)?;
+    let read = file.readv(&mut bufs).await?;
+    Ok(read as u64)
+}
+
+pub async fn writev(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, iov, iovcnt] = context.args;
+    let mut bufs = Vec::new();
+    for iovec in user_iovecs(iov, iovcnt).await? {
+        let buf = user_slice(iovec.base, iovec.len)?;
+        mmap::prefault(iovec.base, iovec.len, false).await?;
+        bufs.push(buf);
+    }
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow()
+        .get(fd)?;
+    let written = file.writev(&bufs).await?;
+    Ok(written as u64)
+}
+
+pub async fn open(context: &SyscallContext) -> SysResult<u64> {
+    let [path, flags, _] = context.args;
+    let path = user_path(path)?;
+    let flags = flags as u32;
+    let table = process_fd_table(context.thread_id).await?;
+    let file = VIRTUAL_FS.lock().await.open(path, flags).await?;
+    let file = Arc::new(OpenFile::new(file, flags));
+    let res = table
+        .borrow_mut()
+        .insert(file.clone(), flags & O_CLOEXEC != 0);
+    if res.is_err() {
+        release(file).await;
+    }
+    res
+}
+
+pub async fn close(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, _, _] = context.args;
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow_mut()
+        .remove(fd)?;
+    release(file).await;
+    Ok(0)
+}
+
+pub async fn lseek(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, offset, whence] = context.args;
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow()
+        .get(fd)?;
+    let offset = file.lseek(offset as i64, whence as u32).await?;
+    Ok(offset)
+}
+
+// The file type is part of the mode
+#[repr(C)]
+struct UserStat {
+    mode: u32,
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    size: u64,
+    block: u64,
+    mtime: i64,
+}
+
+async fn write_stat(buf: u64, stat: &Stat) -> SysResult<u64> {
+    if buf == 0 {
+        return Err(Errno::EFAULT);
+    }
+    mmap::prefault(buf, core::mem::size_of::<UserStat>() as u64, true).await?;
+    let user_stat = UserStat {
+        mode: stat.mode,
+        nlink: stat.nlink,
+        uid: stat.uid,
+        gid: stat.gid,
+        size: stat.size,
+        block: stat.block,
+        mtime: stat.mtime,
+    };
+    unsafe { (buf as *mut UserStat).write_unaligned(user_stat) };
+    Ok(0)
+}
+
+pub async fn stat(context: &SyscallContext) -> SysResult<u64> {
+    let [path, buf, _] = context.args;
+    let stat = VIRTUAL_FS.lock().await.stat(user_path(path)?).await?;
+    write_stat(buf, &stat).await
+}
+
+pub async fn fstat(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, buf, _] = context.args;
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow()
+        .get(fd)?;
+    let stat = file.fd.lock().await.fstat().await?;
+    write_stat(buf, &stat).await
+}
+
+// The target is truncated to the buffer, without a terminating NUL
+pub async fn readlink(context: &SyscallContext) -> SysResult<u64> {
+    let [path, buf, size] = context.args;
+    let buf = user_slice_mut(buf, size)?;
+    mmap::prefault(buf.as_ptr() as u64, size, true).await?;
+    let target = VIRTUAL_FS.lock().await.readlink(user_path(path)?).await?;
+    let len = core::cmp::min(target.len(), buf.len());
+    buf[..len].copy_from_slice(&target.as_bytes()[..len]);
+    Ok(len as u64)
+}
+
+pub async fn mkdir(context: &SyscallContext) -> SysResult<u64> {
+    let [path, _, _] = context.args;
+    let path = user_path(path)?;
+    VIRTUAL_FS.lock().await.mkdir(path).await.map(|_| 0)
+}
+
+pub async fn unlink(context: &SyscallContext) -> SysResult<u64> {
+    let [path, _, _] = context.args;
+    let path = user_path(path)?;
+    VIRTUAL_FS.lock().await.unlink(path).await.map(|_| 0)
+}
+
+pub async fn rmdir(context: &SyscallContext) -> SysResult<u64> {
+    let [path, _, _] = context.args;
+    let path = user_path(path)?;
+    VIRTUAL_FS.lock().await.rmdir(path).await.map(|_| 0)
+}
+
+pub async fn ftruncate(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, length, _] = context.args;
+    let length = usize::try_from(length).map_err(|_| Errno::EINVAL)?;
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow()
+        .get(fd)?;
+    let res = file.fd.lock().await.ftruncate(length).await;
+    res.map(|_| 0)
+}
+
+// The read and write descriptors are stored in the two ints at fds
+pub async fn pipe(context: &SyscallContext) -> SysResult<u64> {
+    let [fds, _, _] = context.args;
+    if fds == 0 {
+        return Err(Errno::EFAULT);
+    }
+    let table = process_fd_table(context.thread_id).await?;
+    let (reader, writer) = pipe::pipe().await;
+    let reader = Arc::new(OpenFile::new(reader, O_RDONLY));
+    let writer = Arc::new(OpenFile::new(writer, O_WRONLY));
+
+    let res = {
+        let mut table = table.borrow_mut();
+        table.insert(reader.clone(), false).and_then(|read_fd| {
+            match table.insert(writer.clone(), false) {
+                Ok(write_fd) => Ok((read_fd, write_fd)),
+                Err(err) => {
+                    let _ = table.remove(read_fd);
+                    Err(err)
+                }
+            }
+        })
+    };
+    let (read_fd, write_fd) = match res {
+        Ok(fds) => fds,
+        Err(err) => {
+            release(reader).await;
+            release(writer).await;
+            return Err(err);
+        }
+    };
+
+    let fds = unsafe { core::slice::from_raw_parts_mut(fds as *mut i32, 2) };
+    fds[0] = read_fd as i32;
+    fds[1] = write_fd as i32;
+    Ok(0)
+}
+
+// struct pollfd of the C library
+#[repr(C)]
+struct UserPollFd {
+    fd: i32,
+    events: u16,
+    revents: u16,
+}
+
+// The timeout is in PIT ticks, negative to wait forever
+pub async fn poll(context: &SyscallContext) -> SysResult<u64> {
+    let [fds, nfds, timeout] = context.args;
+    if fds == 0 && nfds > 0 {
+        return Err(Errno::EFAULT);
+    }
+    let user_fds = match nfds {
+        0 => &mut [],
+        _ => unsafe { core::slice::from_raw_parts_mut(fds as *mut UserPollFd, nfds as usize) },
+    };
+    let timeout = match timeout as i64 {
+        t if t < 0 => None,
+        t => Some(t as u64),
+    };
+
+    // Descriptors not open are reported at once, without waiting
+    let table = process_fd_table(context.thread_id).await?;
+    let mut pollfds = Vec::new();
+    let mut indexes = Vec::new(); // Position of each pollfd in user_fds
+    let mut invalid: u64 = 0;
+    for (i, user_fd) in user_fds.iter_mut().enumerate() {
+        user_fd.revents = 0;
+        if user_fd.fd < 0 {
+            continue;
+        }
+        match table.borrow().get(user_fd.fd as u64) {
+            Ok(file) => {
+                pollfds.push(PollFd {
+                    fd: file.fd.clone(),
+                    events: user_fd.events,
+                    revents: 0,
+                });
+                indexes.push(i);
+            }
+            Err(_) => {
+                user_fd.revents = POLLNVAL;
+                invalid += 1;
+            }
+        }
+    }
+    let timeout = match invalid {
+        0 => timeout,
+        _ => Some(0),
+    };
+
+    let ready = poll::poll(&mut pollfds, timeout).await as u64;
+    for (pollfd, i) in pollfds.iter().zip(indexes) {
+        user_fds[i].revents = pollfd.revents;
+    }
+    Ok(ready + invalid)
+}
+
+// The six arguments are passed in memory, as with the i386 old_mmap
+#[repr(C)]
+struct MmapArgs {
+    addr: u64, // Hint, ignored
+    len: u64,
+    prot: u64,
+    flags: u64,
+    fd: u64,
+    offset: u64,
+}
+
+pub async fn mmap(context: &SyscallContext) -> SysResult<u64> {
+    let [args, _, _] = context.args;
+    if args == 0 {
+        return Err(Errno::EFAULT);
+    }
+    let args = unsafe { &*(args as *const MmapArgs) };
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow()
+        .get(args.fd)?;
+    mmap::mmap(
+        file,
+        args.len,
+        args.prot as u32,
+        args.flags as u32,
+        args.offset,
+    )
+    .await
+}
+
+pub async fn munmap(context: &SyscallContext) -> SysResult<u64> {
+    let [addr, len, _] = context.args;
+    mmap::munmap(addr, len).await.map(|_| 0)
+}
+
+pub async fn msync(context: &SyscallContext) -> SysResult<u64> {
+    let [addr, len, _] = context.args;
+    mmap::msync(addr, len).await.map(|_| 0)
+}
+
+pub async fn umount(context: &SyscallContext) -> SysResult<u64> {
+    let [target, _, _] = context.args;
+    fs::umount(user_path(target)?).await.map(|_| 0)
+}
+
+// Records written by getdents: the file size, the record length, the entry
+// type and the NUL terminated name, padded to 8 bytes
+const DIRENT_HEADER_LEN: usize = 11;
+
+fn dirent_len(entry: &DirEntry) -> usize {
+    (DIRENT_HEADER_LEN + entry.name.len() + 1 + 7) & !7
+}
+
+fn write_dirent(buf: &mut [u8], entry: &DirEntry) {
+    let file_type = match entry.file_type {
+        FileType::File => DT_REG,
+        FileType::Directory => DT_DIR,
+        FileType::Symlink => DT_LNK,
+        FileType::CharDevice => DT_CHR,
+        FileType::BlockDevice => DT_BLK,
+        FileType::Fifo => DT_FIFO,
+    };
+    let name = entry.name.as_bytes();
+    let reclen = buf.len() as u16;
+    buf.fill(0);
+    buf[..8].copy_from_slice(&entry.size.to_le_bytes());
+    buf[8..10].copy_from_slice(&reclen.to_le_bytes());
+    buf[10] = file_type;
+    buf[DIRENT_HEADER_LEN..DIRENT_HEADER_LEN + name.len()].copy_from_slice(name);
+}
+
+// Entries are written while they fit, 0 is returned at the end of the directory
+pub async fn getdents(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, buf, count] = context.args;
+    let buf = user_slice_mut(buf, count)?;
+    mmap::prefault(buf.as_ptr() as u64, count, true).await?;
+    let file = process_fd_table(context.thread_id)
+        .await?
+        .borrow()
+        .get(fd)?;
+
+    let mut written: usize = 0;
+    while let Some(entry) = file.readdir().await? {
+        let len = dirent_len(&entry);
+        if written + len > buf.len() {
+            file.unread(entry);
+            // Not even one entry fits
+            if written == 0 {
+                return Err(Errno::EINVAL);
+            }
+            break;
+        }
+        write_dirent(&mut buf[written..written + len], &entry);
+        written += len;
+    }
+    Ok(written as u64)
+}
+
+pub async fn dup(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, _, _] = context.args;
+    process_fd_table(context.thread_id)
+        .await?
+        .borrow_mut()
+        .dup(fd)
+}
+
+// The descriptor previously open as new_fd is closed
+pub async fn dup2(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, new_fd, _] = context.args;
+    let table = process_fd_table(context.thread_id).await?;
+    let (new_fd, replaced) = table.borrow_mut().dup2(fd, new_fd)?;
+    if let Some(file) = replaced {
+        release(file).await;
+    }
+    Ok(new_fd)
+}
+
+pub async fn fcntl(context: &SyscallContext) -> SysResult<u64> {
+    let [fd, cmd, arg] = context.args;
+    let table = process_fd_table(context.thread_id).await?;
+    let mut table = table.borrow_mut();
+    match cmd {
+        F_DUPFD => table.dup_from(fd, arg),
+        F_DUPFD_CLOEXEC => {
+            let new_fd = table.dup_from(fd, arg)?;
+            table.set_cloexec(new_fd, true)?;
+            Ok(new_fd)
+        }
+        F_GETFD => match table.cloexec(fd)? {
+            true => Ok(FD_CLOEXEC),
+            false => Ok(0),
+        },
+        F_SETFD => table.set_cloexec(fd, arg & FD_CLOEXEC != 0).map(|_| 0),
+        F_GETFL => Ok(table.get(fd)?.flags() as u64),
+        F_SETFL => {
+            table.get(fd)?.set_flags(arg as u32);
+            Ok(0)
+        }
+        _ => Err(Errno::EINVAL),
+    }
+}
+
+// Kernel threads share the kernel address space, their pointers are used as is
+fn user_slice(ptr: u64, len: u64) -> SysResult<&'static [u8]> {
+    if ptr == 0 {
+        return Err(Errno::EFAULT);
+    }
+    Ok(unsafe { core::slice::from_raw_parts(ptr as *const u8, len as usize) })
+}
+
+fn user_slice_mut(ptr: u64, len: u64) -> SysResult<&'static mut [u8]> {
+    if ptr == 0 {
+        return Err(Errno::EFAULT);
+    }
+    Ok(unsafe { core::slice::from_raw_parts_mut(ptr as *mut u8, len as usize) })
+}
+
+// struct iovec of the C library
+#[repr(C)]
+struct UserIovec {
+    base: u64,
+    len: u64,
+}
+
+const IOV_MAX: u64 = 1024;
+
+async fn user_iovecs(iov: u64, iovcnt: u64) -> SysResult<&'static [UserIovec]> {
+    if iovcnt > IOV_MAX {
+        return Err(Errno::EINVAL);
+    }
+    if iovcnt == 0 {
+        return Ok(&[]);
+    }
+    if iov == 0 {
+        return Err(Errno::EFAULT);
+    }
+    let len = iovcnt * core::mem::size_of::<UserIovec>() as u64;
+    mmap::prefault(iov, len, false).await?;
+    Ok(unsafe { core::slice::from_raw_parts(iov as *const UserIovec, iovcnt as usize) })
+}
+
+// Paths are NUL terminated UTF-8 strings, the NUL included in PATH_MAX
+const PATH_MAX: usize = 4096;
+
+fn user_path(ptr: u64) -> SysResult<&'static str> {
+    if ptr == 0 {
+        return Err(Errno::EFAULT);
+    }
+    let mut len = 0;
+    while unsafe { *(ptr as *const u8).add(len) } != 0 {
+        len += 1;
+        if len >= PATH_MAX {
+            return Err(Errno::ENAMETOOLONG);
+        }
+    }
+    let bytes = unsafe { core::slice::from_raw_parts(ptr as *const u8, len) };
+    core::str::from_utf8(bytes).map_err(|_| Errno::EINVAL)
+}
@@ -4,11 +4,13 @@ use crate::proc::scheduler::SCHEDULER;
 use crate::task::yield_executor::YieldExecutor;
 use crate::task::Task;
 
+use errno::{syscall_result, Errno};
 pub use ids::*;
 
 use alloc::sync::Arc;
 use core::cell::RefCell;
 
+pub mod errno;
 pub mod ids;
 pub mod io;
 pub mod proc;
@@ -17,6 +19,7 @@ pub type SyscallContextT = Arc<RefCell<SyscallContext>>;
 
 pub struct SyscallContext {
     id: SyscallId,
+    args: [u64; 3],
     res: u64,
     thread_id: crate::proc::thread::ThreadId,
 }
@@ -30,7 +33,31 @@ impl SyscallContext {
     pub async fn dispatch(&mut self) {
         match self.id {
             EXIT_ID => proc::exit(self).await,
-            _ => bad_syscall().await,
+            READ_ID => self.res = syscall_result(io::read(self).await),
+            WRITE_ID => self.res = syscall_result(io::write(self).await),
+            OPEN_ID => self.res = syscall_result(io::open(self).await),
+            CLOSE_ID => self.res = syscall_result(io::close(self).await),
+            LSEEK_ID => self.res = syscall_result(io::lseek(self).await),
+            DUP_ID => self.res = syscall_result(io::dup(self).await),
+            DUP2_ID => self.res = syscall_result(io::dup2(self).await),
+            FCNTL_ID => self.res = syscall_result(io::fcntl(self).await),
+            PIPE_ID => self.res = syscall_result(io::pipe(self).await),
+            POLL_ID => self.res = syscall_result(io::poll(self).await),
+            MMAP_ID => self.res = syscall_result(io::mmap(self).await),
+            MUNMAP_ID => self.res = syscall_result(io::munmap(self).await),
+            MSYNC_ID => self.res = syscall_result(io::msync(self).await),
+            UMOUNT_ID => self.res = syscall_result(io::umount(self).await),
+            GETDENTS_ID => self.res = syscall_result(io::getdents(self).await),
+            STAT_ID => self.res = syscall_result(io::stat(self).await),
+            FSTAT_ID => self.res = syscall_result(io::fstat(self).await),
+            READLINK_ID => self.res = syscall_result(io::readlink(self).await),
+            MKDIR_ID => self.res = syscall_result(io::mkdir(self).await),
+            UNLINK_ID => self.res = syscall_result(io::unlink(self).await),
+            RMDIR_ID => self.res = syscall_result(io::rmdir(self).await),
+            FTRUNCATE_ID => self.res = syscall_result(io::ftruncate(self).await),
+            READV_ID => self.res = syscall_result(io::readv(self).await),
+            WRITEV_ID => self.res = syscall_result(io::writev(self).await),
+            _ => self.res = Errno::ENOSYS.to_syscall_result(),
         }
     }
 }
@@ -40,10 +67,11 @@ async fn syscall_runner(context: SyscallContextT) {
     context.borrow_mut().run().await;
 }
 
-pub fn syscall_routine(syscall_id: SyscallId) -> u64 {
+pub fn syscall_routine(syscall_id: SyscallId, args: [u64; 3]) -> u64 {
     println!("Running syscall interrupt handler");
     let context: SyscallContextT = Arc::new(RefCell::new(SyscallContext {
         id: syscall_id,
+        args,
         res: 0,
         thread_id: *RUNNING_THREAD.try_lock().unwrap(),
     }));
@@ -56,5 +84,3 @@ pub fn syscall_routine(syscall_id: SyscallId) -> u64 {
     let res = context.borrow().res;
     res
 }
-
-async fn bad_syscall() {}
@@ -1,3 +1,4 @@
+use crate::fd::table::release_process_fd_table;
 use crate::println;
 use crate::proc::scheduler::SCHEDULER;
 use crate::proc::thread::resume_k_thread;
@@ -6,6 +7,7 @@ use super::SyscallContext;
 
 pub async fn exit(context: &SyscallContext) {
     println!("Running exit(2)");
+    release_process_fd_table(context.thread_id).await;
     SCHEDULER.lock().await.exit(context.thread_id);
     resume_k_thread();
 }
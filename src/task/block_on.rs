@@ -0,0 +1,61 @@
+use alloc::{boxed::Box, sync::Arc, task::Wake};
+use core::future::Future;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use x86_64::instructions::interrupts::{self, enable_and_hlt};
+
+static IN_BLOCK_ON: AtomicBool = AtomicBool::new(false);
+
+// Futures run from an exception handler cannot wait for the executor tasks
+pub fn in_block_on() -> bool {
+    IN_BLOCK_ON.load(Ordering::Acquire)
+}
+
+struct FlagWaker {
+    woken: AtomicBool,
+}
+
+impl Wake for FlagWaker {
+    fn wake(self: Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+    }
+}
+
+// Run a future to completion from an exception handler, halting until an
+// interrupt wakes it. The executor tasks do not run meanwhile, the future
+// must not wait on locks they may hold, see AsyncMutex::lock_or_fail.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let flag = Arc::new(FlagWaker {
+        woken: AtomicBool::new(true),
+    });
+    let waker = Waker::from(flag.clone());
+    let mut context = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    let were_enabled = interrupts::are_enabled();
+    let was_in_block_on = IN_BLOCK_ON.swap(true, Ordering::AcqRel);
+
+    let res = loop {
+        if flag.woken.swap(false, Ordering::AcqRel) {
+            interrupts::enable();
+            if let Poll::Ready(res) = future.as_mut().poll(&mut context) {
+                break res;
+            }
+        }
+        interrupts::disable();
+        if flag.woken.load(Ordering::Acquire) {
+            interrupts::enable();
+        } else {
+            enable_and_hlt();
+        }
+    };
+
+    IN_BLOCK_ON.store(was_in_block_on, Ordering::Release);
+    if !were_enabled {
+        interrupts::disable();
+    }
+    res
+}
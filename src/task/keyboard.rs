@@ -1,18 +1,26 @@
+use crate::fs::{FileSystem, VIRTUAL_FS};
+use crate::syscalls::io::O_RDONLY;
 use crate::{print, println};
 
+use alloc::vec::Vec;
 use conquer_once::spin::OnceCell;
 use core::{
     pin::Pin,
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
 };
 use crossbeam_queue::ArrayQueue;
-use futures_util::stream::{Stream, StreamExt};
-use futures_util::task::AtomicWaker;
+use futures_util::stream::Stream;
+use lazy_static::lazy_static;
 use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
 
 static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
 
-static WAKER: AtomicWaker = AtomicWaker::new();
+lazy_static! {
+    // Every reader waiting for a scancode, woken together by the next one
+    static ref WAKERS: Mutex<Vec<Waker>> = Mutex::new(Vec::new());
+}
 
 pub struct ScancodeStream {
     _private: (), // Makes ScancodeStream constructable only
@@ -20,10 +28,9 @@ pub struct ScancodeStream {
 }
 
 impl ScancodeStream {
+    // Each reader has its own stream, over the shared queue
     pub fn new() -> Self {
-        SCANCODE_QUEUE
-            .try_init_once(|| ArrayQueue::new(100))
-            .expect("ScancodeStream::new should only be called once");
+        let _ = SCANCODE_QUEUE.try_init_once(|| ArrayQueue::new(100));
         ScancodeStream { _private: () }
     }
 }
@@ -40,24 +47,36 @@ impl Stream for ScancodeStream {
             return Poll::Ready(Some(scancode));
         }
 
-        WAKER.register(&cx.waker());
+        // Registered with interrupts disabled, the handler never finds the lock taken
+        interrupts::without_interrupts(|| {
+            let mut wakers = WAKERS.lock();
+            if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+        });
 
         match queue.pop() {
-            Ok(scancode) => {
-                WAKER.take();
-                Poll::Ready(Some(scancode))
-            }
+            Ok(scancode) => Poll::Ready(Some(scancode)),
             Err(crossbeam_queue::PopError) => Poll::Pending,
         }
     }
 }
 
+// Scancodes are queued, waiting to be read
+pub(crate) fn scancode_pending() -> bool {
+    SCANCODE_QUEUE
+        .try_get()
+        .map_or(false, |queue| !queue.is_empty())
+}
+
 pub(crate) fn add_scancode(scancode: u8) {
     if let Ok(queue) = SCANCODE_QUEUE.try_get() {
         if let Err(_) = queue.push(scancode) {
             println!("Keyboard scancode queue full, dropping input {}", scancode);
-        } else {
-            WAKER.wake();
+        } else if let Some(mut wakers) = WAKERS.try_lock() {
+            for waker in wakers.drain(..) {
+                waker.wake();
+            }
         }
     } else {
         println!("Keyboard scancode queue uninitialized");
@@ -65,12 +84,19 @@ pub(crate) fn add_scancode(scancode: u8) {
 }
 
 pub async fn print_keypresses() {
-    let mut scancodes = ScancodeStream::new();
+    let kbd = match VIRTUAL_FS.lock().await.open("/dev/kbd", O_RDONLY).await {
+        Ok(fd) => fd,
+        Err(err) => {
+            println!("Could not open /dev/kbd: {}", err);
+            return;
+        }
+    };
     let mut keyboard: Keyboard<layouts::Us104Key, ScancodeSet1> =
         Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore);
 
-    while let Some(scancode) = scancodes.next().await {
-        if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
+    let mut scancode: [u8; 1] = [0];
+    while let Ok(1) = kbd.lock().await.read(&mut scancode, 1).await {
+        if let Ok(Some(key_event)) = keyboard.add_byte(scancode[0]) {
             if let Some(key) = keyboard.process_keyevent(key_event) {
                 match key {
                     DecodedKey::Unicode(character) => print!("{}", character),
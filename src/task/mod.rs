@@ -4,6 +4,7 @@ use core::pin::Pin;
 use core::sync::atomic::{AtomicU64, Ordering};
 use core::task::{Context, Poll};
 
+pub mod block_on;
 pub mod executor;
 pub mod yield_executor;
 pub mod keyboard;
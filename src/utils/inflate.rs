@@ -0,0 +1,298 @@
+// DEFLATE (RFC 1951) decoder, for zlib (RFC 1950) streams. Huffman codes are
+// decoded one bit at a time from their canonical form, as in zlib's puff.
+
+use alloc::vec::Vec;
+
+const MAX_BITS: usize = 15; // Longest code
+const MAX_LIT_CODES: usize = 288;
+const MAX_DIST_CODES: usize = 30;
+const FIXED_LIT_CODES: usize = 288;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// Order the code length code lengths are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+// zlib header
+const CM_DEFLATE: u8 = 8;
+const FDICT: u8 = 0x20;
+const ADLER_MOD: u32 = 65521;
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,   // Next byte
+    bitbuf: u32,  // Bits not consumed yet, least significant first
+    bitcount: u8, // Number of bits in bitbuf
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data,
+            pos: 0,
+            bitbuf: 0,
+            bitcount: 0,
+        }
+    }
+
+    fn bits(&mut self, count: u8) -> Option<u32> {
+        let mut value = self.bitbuf;
+        while self.bitcount < count {
+            let byte = *self.data.get(self.pos)? as u32;
+            self.pos += 1;
+            value |= byte << self.bitcount;
+            self.bitcount += 8;
+        }
+        self.bitbuf = value >> count;
+        self.bitcount -= count;
+        Some(value & ((1u32 << count) - 1))
+    }
+
+    // Stored blocks start on a byte boundary
+    fn align(&mut self) {
+        self.bitbuf = 0;
+        self.bitcount = 0;
+    }
+}
+
+// Canonical Huffman code, symbols ordered by code length then value
+struct Huffman {
+    counts: [u16; MAX_BITS + 1], // Number of codes of each length
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    // Incomplete codes are allowed, over-subscribed ones are not
+    fn new(lengths: &[u8]) -> Option<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left <<= 1;
+            left -= count as i32;
+            if left < 0 {
+                return None;
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = alloc::vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Some(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Option<u16> {
+        let mut code: i32 = 0; // Bits read so far
+        let mut first: i32 = 0; // First code of the current length
+        let mut index: i32 = 0; // Index of the first symbol of the current length
+        for len in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+fn stored(reader: &mut BitReader, out: &mut Vec<u8>, max_len: usize) -> Option<()> {
+    reader.align();
+    let header = reader.data.get(reader.pos..reader.pos + 4)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let nlen = u16::from_le_bytes([header[2], header[3]]);
+    if len != !nlen {
+        return None;
+    }
+    reader.pos += 4;
+    let data = reader.data.get(reader.pos..reader.pos + len as usize)?;
+    if out.len() + data.len() > max_len {
+        return None;
+    }
+    out.extend_from_slice(data);
+    reader.pos += len as usize;
+    Some(())
+}
+
+fn codes(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    max_len: usize,
+    lit: &Huffman,
+    dist: &Huffman,
+) -> Option<()> {
+    // Checked before writing, the output never grows past its allocation
+    loop {
+        let symbol = lit.decode(reader)? as usize;
+        if symbol < 256 {
+            if out.len() >= max_len {
+                return None;
+            }
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Some(());
+        } else {
+            let symbol = symbol - 257;
+            let len =
+                *LENGTH_BASE.get(symbol)? as usize + reader.bits(LENGTH_EXTRA[symbol])? as usize;
+            let symbol = dist.decode(reader)? as usize;
+            let distance =
+                *DIST_BASE.get(symbol)? as usize + reader.bits(DIST_EXTRA[symbol])? as usize;
+            if distance > out.len() || out.len() + len > max_len {
+                return None;
+            }
+            // The copy may overlap the bytes it produces
+            let start = out.len() - distance;
+            for i in 0..len {
+                let byte = out[start + i];
+                out.push(byte);
+            }
+        }
+    }
+}
+
+fn fixed(reader: &mut BitReader, out: &mut Vec<u8>, max_len: usize) -> Option<()> {
+    let mut lengths = [0u8; FIXED_LIT_CODES];
+    for (symbol, len) in lengths.iter_mut().enumerate() {
+        *len = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    let lit = Huffman::new(&lengths)?;
+    let dist = Huffman::new(&[5; MAX_DIST_CODES])?;
+    codes(reader, out, max_len, &lit, &dist)
+}
+
+fn dynamic(reader: &mut BitReader, out: &mut Vec<u8>, max_len: usize) -> Option<()> {
+    let nlit = reader.bits(5)? as usize + 257;
+    let ndist = reader.bits(5)? as usize + 1;
+    let ncode = reader.bits(4)? as usize + 4;
+    if nlit > MAX_LIT_CODES || ndist > MAX_DIST_CODES {
+        return None;
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in CODE_LENGTH_ORDER[..ncode].iter() {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    // Literal/length and distance code lengths, run length encoded together
+    let mut lengths = [0u8; MAX_LIT_CODES + MAX_DIST_CODES];
+    let mut index = 0;
+    while index < nlit + ndist {
+        let symbol = code_length_code.decode(reader)?;
+        let (len, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 if index > 0 => (lengths[index - 1], 3 + reader.bits(2)? as usize),
+            17 => (0, 3 + reader.bits(3)? as usize),
+            18 => (0, 11 + reader.bits(7)? as usize),
+            _ => return None,
+        };
+        if index + repeat > nlit + ndist {
+            return None;
+        }
+        lengths[index..index + repeat].fill(len);
+        index += repeat;
+    }
+    // The end of block code is required
+    if lengths[256] == 0 {
+        return None;
+    }
+
+    let lit = Huffman::new(&lengths[..nlit])?;
+    let dist = Huffman::new(&lengths[nlit..nlit + ndist])?;
+    codes(reader, out, max_len, &lit, &dist)
+}
+
+// Decompress a raw DEFLATE stream, returning the data and the bytes consumed.
+// Streams producing more than max_len bytes are rejected. The output is
+// allocated once, growing it would need twice its size at the end.
+pub fn inflate(data: &[u8], max_len: usize) -> Option<(Vec<u8>, usize)> {
+    let mut reader = BitReader::new(data);
+    let mut out = Vec::new();
+    out.try_reserve_exact(max_len).ok()?;
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored(&mut reader, &mut out, max_len)?,
+            1 => fixed(&mut reader, &mut out, max_len)?,
+            2 => dynamic(&mut reader, &mut out, max_len)?,
+            _ => return None,
+        }
+        if out.len() > max_len {
+            return None;
+        }
+        if last {
+            return Some((out, reader.pos));
+        }
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= ADLER_MOD;
+        b %= ADLER_MOD;
+    }
+    (b << 16) | a
+}
+
+// Decompress a zlib stream, checking its header and checksum
+pub fn zlib_decompress(data: &[u8], max_len: usize) -> Option<Vec<u8>> {
+    let (&cmf, rest) = data.split_first()?;
+    let (&flg, rest) = rest.split_first()?;
+    if cmf & 0xf != CM_DEFLATE
+        || cmf >> 4 > 7
+        || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31)
+    {
+        return None;
+    }
+    // Preset dictionaries are not supported
+    if flg & FDICT != 0 {
+        return None;
+    }
+
+    let (out, consumed) = inflate(rest, max_len)?;
+    let checksum = rest.get(consumed..consumed + 4)?;
+    match u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) {
+        sum if sum == adler32(&out) => Some(out),
+        _ => None,
+    }
+}
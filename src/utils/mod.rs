@@ -1,3 +1,4 @@
+pub mod inflate;
 pub mod mutex;
 pub mod serialize;
 
@@ -10,10 +11,3 @@ pub fn ref_offset<T>(r: &T, off: isize) -> &T {
         return &*ref_ptr.offset(off);
     }
 }
-
-pub fn ref_raw_offset<T>(r: &T, off: isize) -> &T {
-    let ref_ptr: *const T = r;
-    unsafe {
-        return &*ref_ptr.cast::<u8>().offset(off).cast::<T>();
-    }
-}
@@ -1,18 +1,21 @@
-use alloc::sync::Arc;
+use crate::task::block_on::in_block_on;
+
+use alloc::{sync::Arc, vec::Vec};
 use core::cell::UnsafeCell;
 use core::future::Future;
 use core::ops::{Deref, DerefMut, Drop};
 use core::pin::Pin;
 use core::sync::atomic::{AtomicBool, Ordering};
-use core::task::{Context, Poll};
-
-use futures_util::task::AtomicWaker;
+use core::task::{Context, Poll, Waker};
+use x86_64::instructions::interrupts;
 
 #[derive(Clone)]
 struct Lock {
     lock: Arc<AtomicBool>,
-    // TODO: Make a queue of wakers
-    waker: Arc<AtomicWaker>,
+    // Every task waiting for the lock, woken when it is released. Guards may
+    // be dropped by exception handlers, the list is only locked with
+    // interrupts disabled.
+    wakers: Arc<spin::Mutex<Vec<Waker>>>,
 }
 
 pub struct AsyncMutex<T: ?Sized> {
@@ -22,7 +25,7 @@ pub struct AsyncMutex<T: ?Sized> {
 
 pub struct AsyncMutexGuard<'a, T>
 where
-    T: 'a,
+    T: 'a + ?Sized,
 {
     mutex: &'a AsyncMutex<T>,
 }
@@ -31,7 +34,7 @@ impl Lock {
     fn new() -> Self {
         Lock {
             lock: Arc::new(AtomicBool::new(false)),
-            waker: Arc::new(AtomicWaker::new()),
+            wakers: Arc::new(spin::Mutex::new(Vec::new())),
         }
     }
 
@@ -41,7 +44,20 @@ impl Lock {
 
     fn drop(&self) {
         self.lock.swap(false, Ordering::Release);
-        self.waker.wake();
+        let wakers: Vec<Waker> =
+            interrupts::without_interrupts(|| self.wakers.lock().drain(..).collect());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        interrupts::without_interrupts(|| {
+            let mut wakers = self.wakers.lock();
+            if !wakers.iter().any(|w| w.will_wake(waker)) {
+                wakers.push(waker.clone());
+            }
+        });
     }
 }
 
@@ -53,13 +69,10 @@ impl Future for Lock {
             return Poll::Ready(());
         }
 
-        self.waker.register(&cx.waker());
+        self.register(cx.waker());
 
         match self.try_lock() {
-            false => {
-                self.waker.take();
-                Poll::Ready(())
-            }
+            false => Poll::Ready(()),
             true => Poll::Pending,
         }
     }
@@ -72,7 +85,9 @@ impl<T> AsyncMutex<T> {
             inner: UnsafeCell::new(val),
         }
     }
+}
 
+impl<T: ?Sized> AsyncMutex<T> {
     pub fn try_lock(&self) -> Option<AsyncMutexGuard<T>> {
         if !self.lock.try_lock() {
             Some(AsyncMutexGuard { mutex: self })
@@ -89,25 +104,34 @@ impl<T> AsyncMutex<T> {
         self.lock.clone().await;
         AsyncMutexGuard { mutex: self }
     }
+
+    // Locks taken by futures run from exception handlers through block_on
+    // are only tried: the task holding one would not run to release it
+    pub async fn lock_or_fail(&self) -> Option<AsyncMutexGuard<'_, T>> {
+        match in_block_on() {
+            true => self.try_lock(),
+            false => Some(self.lock().await),
+        }
+    }
 }
 
-unsafe impl<T> Send for AsyncMutex<T> {}
-unsafe impl<T> Sync for AsyncMutex<T> {}
+unsafe impl<T: ?Sized> Send for AsyncMutex<T> {}
+unsafe impl<T: ?Sized> Sync for AsyncMutex<T> {}
 
-impl<T> Drop for AsyncMutexGuard<'_, T> {
+impl<T: ?Sized> Drop for AsyncMutexGuard<'_, T> {
     fn drop(&mut self) {
         self.mutex.lock.drop();
     }
 }
 
-impl<T> Deref for AsyncMutexGuard<'_, T> {
+impl<T: ?Sized> Deref for AsyncMutexGuard<'_, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         unsafe { &*self.mutex.inner.get() }
     }
 }
 
-impl<T> DerefMut for AsyncMutexGuard<'_, T> {
+impl<T: ?Sized> DerefMut for AsyncMutexGuard<'_, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         unsafe { &mut *self.mutex.inner.get() }
     }